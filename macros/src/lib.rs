@@ -0,0 +1,5099 @@
+
+#![crate_name = "double_dyn_macros"]
+
+//! Implementation of the `double_dyn!` macro.  This crate should be used through the
+//! [double-dyn](https://crates.io/crates/double-dyn) crate, which also provides the types referenced by the
+//! generated code.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{HashMap, HashSet};
+
+use proc_macro2::token_stream::IntoIter as TokenIter;
+use proc_macro2::{*};
+use quote::{quote, quote_spanned};
+use heck::{AsShoutySnakeCase, AsSnakeCase, AsUpperCamelCase};
+
+mod parse;
+use crate::parse::*;
+
+/// Emits traits and functions to enable multiple dynamic argument dispatch
+#[proc_macro]
+pub fn double_dyn(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+
+    let output = match double_dyn_internal(input.into()) {
+        Ok(expanded) => expanded,
+        Err(error) => error.into_compile_error(),
+    };
+
+    output.into()
+}
+
+/// Marks an existing trait, or an impl of it, so that a `double_dyn!` invocation can add its methods
+#[proc_macro_attribute]
+pub fn methods(args: proc_macro::TokenStream, input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+
+    let output = match methods_internal(args.into(), input.into()) {
+        Ok(expanded) => expanded,
+        Err(error) => error.into_compile_error(),
+    };
+
+    output.into()
+}
+
+/// Emits a `macro_rules!` callback that carries the declarations of a `double_dyn!` invocation, so the `impl` blocks
+/// can be supplied later by `double_dyn_impl!`
+#[proc_macro]
+pub fn double_dyn_decl(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+
+    let output = match double_dyn_decl_internal(input.into()) {
+        Ok(expanded) => expanded,
+        Err(error) => error.into_compile_error(),
+    };
+
+    output.into()
+}
+
+/// Expands a `double_dyn!` invocation from the declarations carried by a `double_dyn_decl!` callback, and the `impl`
+/// blocks passed to this macro.  With `#![collect(name)]`, it instead emits a `macro_rules!` named `name` that carries
+/// the `impl` blocks to a later `double_dyn_impl!`
+#[proc_macro]
+pub fn double_dyn_impl(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+
+    let output = match double_dyn_impl_internal(input.into()) {
+        Ok(expanded) => expanded,
+        Err(error) => error.into_compile_error(),
+    };
+
+    output.into()
+}
+
+//Each dispatched arg gets a letter, "A", "B", "C", etc., so that's the limit on the number of dispatched args
+const MAX_DISPATCHED_ARGS: usize = 26;
+
+//A trait defined by the macro invocation, along with the indices of the dispatched args that use it.  An extended
+// trait is defined outside the invocation with the "methods" attribute, so its methods are added through callbacks.
+// An upcast trait also gets a bridge trait, e.g. "AsMyTrait", for upcasting from the traits that extend it
+struct DynTrait {
+    name: Ident,
+    bounds: TokenStream,
+    roles: Vec<usize>,
+    is_extended: bool,
+    is_upcast: bool,
+}
+
+//Maps the snake_case name of each concrete type onto the tokens for the type
+type TypeMap = HashMap<String, TokenStream>;
+
+//Maps the name of each group declared in the invocation onto the types in the group
+type TypeGroups = HashMap<String, Vec<TokenStream>>;
+
+//Maps each fn name onto the (signature, body) of its implementation for a specific combination of types
+type FnImpls = HashMap<String, (FnSignature, TokenStream)>;
+
+//The types in an impl block header, with None in place of each "_" wildcard
+struct ImplTypes {
+    span: Span,
+    type_lists: Vec<Option<Vec<TokenStream>>>,
+    //For each dispatched arg, the index of an earlier arg if the arg repeats its type, e.g. "<T in [i32, f64], T>"
+    bound_roles: Vec<Option<usize>>,
+    is_zipped: bool,
+}
+
+//An impl block, parsed but with any wildcards not yet resolved into types
+struct ImplBlock {
+    span: Span,
+    impl_types: ImplTypes,
+    exclusions: Vec<ImplTypes>,
+    impl_fns: HashMap<String, (FnSignature, Group)>,
+    is_commutative: bool,
+    is_override: bool,
+}
+
+//The impl block that provided the implementation for a combination of types
+#[derive(Clone, Copy)]
+struct ImplSource {
+    span: Span,
+    is_override: bool,
+    wildcards: usize,
+}
+
+impl ImplSource {
+    //Blocks marked #[override] take precedence, followed by the blocks with the fewest "_" wildcards
+    fn precedence(&self) -> (bool, Reverse<usize>) {
+        (self.is_override, Reverse(self.wildcards))
+    }
+}
+
+//How to report combinations of types that have no implementation, as requested by the "exhaustive" attribute
+#[derive(Clone, Copy, PartialEq)]
+enum Exhaustive {
+    Error,
+    Warn,
+}
+
+//How the dispatch is implemented, as requested by the "backend" attribute.  The vtable backend gives each trait a
+// method per function for every combination of the types dispatched before it, while the table backend gives each
+// trait only an ordinal method, and indexes a table of function pointers with the ordinals of the args.  The enum
+// backend emits an enum in place of each trait, with a variant for each type, and matches on the variants.  The
+// registry backend gives each trait only a method to downcast it, and looks up the implementation for the TypeIds of
+// the args in a registry, which more implementations can be added to at runtime
+#[derive(Clone, Copy, PartialEq)]
+enum Backend {
+    Vtable,
+    Table,
+    Enum,
+    Registry,
+}
+
+//The args of an "extend" attribute, which implements the traits of an extensible family, declared in the module at
+// the path, for types that the family's own invocation doesn't know about
+struct Extension {
+    path: TokenStream,
+    types: Vec<TokenStream>,
+}
+
+//The type string used in place of the type of the A arg for the methods of an extensible family that take it as a
+// trait object, e.g. "l2_multiply_dyn"
+const DYN_TYPE_STRING: &str = "dyn";
+
+//How a dispatched arg is passed, e.g. "&dyn MyTraitA", "&mut dyn MyTraitA", "Box<dyn MyTraitA>",
+// "Rc<dyn MyTraitA>", "Arc<dyn MyTraitA>" or "Option<&dyn MyTraitA>"
+#[derive(Clone, Copy, PartialEq)]
+enum ArgKind {
+    Ref,
+    RefMut,
+    Boxed,
+    Rc,
+    Arc,
+    OptionRef,
+}
+
+//The type string used in place of a type for an optional dispatched arg that is None, e.g. in "impl for <Circle, None>"
+const NONE_TYPE_STRING: &str = "none";
+
+impl ArgKind {
+
+    //The receiver for the trait method that dispatches the arg
+    fn receiver(&self) -> TokenStream {
+        match self {
+            ArgKind::Ref | ArgKind::OptionRef => quote! { &self },
+            ArgKind::RefMut => quote! { &mut self },
+            ArgKind::Boxed => quote! { self: Box<Self> },
+            ArgKind::Rc => quote! { self: ::std::rc::Rc<Self> },
+            ArgKind::Arc => quote! { self: ::std::sync::Arc<Self> },
+        }
+    }
+
+    //The type of the arg once it has been dispatched, given the tokens for its concrete type
+    fn concrete_type(&self, type_tokens: &TokenStream) -> TokenStream {
+        match self {
+            ArgKind::Ref | ArgKind::OptionRef => quote! { &#type_tokens },
+            ArgKind::RefMut => quote! { &mut #type_tokens },
+            ArgKind::Boxed => quote! { Box<#type_tokens> },
+            ArgKind::Rc => quote! { ::std::rc::Rc<#type_tokens> },
+            ArgKind::Arc => quote! { ::std::sync::Arc<#type_tokens> },
+        }
+    }
+
+    //Casts the arg from the trait object to its concrete type, without checking that it is the right type
+    fn unchecked_cast(&self, arg_tokens: &TokenStream, type_tokens: &TokenStream) -> TokenStream {
+        match self {
+            ArgKind::Ref => quote! { &*(#arg_tokens as *const _ as *const #type_tokens) },
+            ArgKind::RefMut => quote! { &mut *(#arg_tokens as *mut _ as *mut #type_tokens) },
+            ArgKind::Boxed => quote! { Box::from_raw(Box::into_raw(#arg_tokens) as *mut #type_tokens) },
+            ArgKind::Rc => quote! { ::std::rc::Rc::from_raw(::std::rc::Rc::into_raw(#arg_tokens) as *const #type_tokens) },
+            ArgKind::Arc => quote! { ::std::sync::Arc::from_raw(::std::sync::Arc::into_raw(#arg_tokens) as *const #type_tokens) },
+            ArgKind::OptionRef => quote! { &*(#arg_tokens.unwrap() as *const _ as *const #type_tokens) },
+        }
+    }
+}
+
+//A function prototype from the invocation, along with the index and kind of the arg for each dispatched type
+struct FnPrototype {
+    sig: FnSignature,
+    arg_idxs: Vec<usize>,
+    arg_kinds: Vec<ArgKind>,
+    fallback_body: Option<TokenStream>,
+    exhaustive: Option<Exhaustive>,
+    is_reduce: bool,
+    is_pairs: bool,
+    is_batch: bool,
+    is_static_dispatch: bool,
+}
+
+//Maps each fn name onto its prototype
+type FnSigs = HashMap<String, FnPrototype>;
+
+//Maps (fn name, types of the previously dispatched args) onto the signature of the trait method for the next arg
+type LnSigs = HashMap<(String, Vec<String>), (FnSignature, TokenStream)>;
+
+fn double_dyn_internal(input: TokenStream) -> Result<TokenStream, SyntaxError> {
+
+    //==================================================================================================================
+    // PHASE 1: Parse the Macro Invocation
+    //==================================================================================================================
+
+    //Parse the preamble of the invocation to get the trait names and any trait bounds.  There is one "type" line for
+    // each dispatched arg, e.g. "type A: MyTraitA;", "type B: MyTraitB;", "type C: MyTraitC;", etc.
+    let mut iter = input.into_iter();
+
+    //Attributes for the whole invocation come first, as inner attributes, e.g. "#![exhaustive]"
+    let mut invocation_exhaustive = None;
+    let mut backend = Backend::Vtable;
+    let mut is_extensible = false;
+    let mut extension = None;
+    let mut extensible_span = Span::call_site();
+    while if_inner_attribute(&iter)? {
+        let attribute = require_attribute(&mut iter, Span::call_site())?;
+        match attribute.name.to_string().as_str() {
+            "exhaustive" if attribute.inner => invocation_exhaustive = Some(parse_exhaustive_attribute(&attribute)?),
+            "backend" if attribute.inner => backend = parse_backend_attribute(&attribute)?,
+            "extensible" if attribute.inner && attribute.args.is_none() => {
+                is_extensible = true;
+                extensible_span = attribute.span;
+            },
+            "extend" if attribute.inner => extension = Some(parse_extend_attribute(&attribute)?),
+            _ => return Err(SyntaxError {
+                message: "expected `#![exhaustive]`, `#![backend(..)]`, `#![extensible]` or `#![extend(..)]`".to_string(),
+                span: attribute.span,
+            })
+        }
+    }
+
+    //An extensible family dispatches through the trait methods, so it needs the vtable backend.  The "extend"
+    // attribute is passed along with the declarations of the family, which must be extensible
+    if is_extensible && backend != Backend::Vtable {
+        return Err(SyntaxError {
+            message: "`#![extensible]` requires the vtable backend".to_string(),
+            span: extensible_span,
+        });
+    }
+    if extension.is_some() && !is_extensible {
+        return Err(SyntaxError {
+            message: "`#![extend(..)]` requires a family declared with `#![extensible]`".to_string(),
+            span: Span::call_site(),
+        });
+    }
+
+    let mut traits: Vec<DynTrait> = vec![];
+    let mut role_traits = vec![];
+    loop {
+
+        //A type line may have a "methods" attribute, if the trait is defined outside the invocation, and an "upcast"
+        // attribute.  Any other attributes belong to the first function
+        let mut temp_iter = iter.clone();
+        let attributes = require_attributes(&mut temp_iter, Span::call_site())?;
+        if role_traits.len() >= 2 && !if_keyword(&mut temp_iter, "type")? {
+            break;
+        }
+        iter = temp_iter;
+        let mut is_extended = false;
+        let mut is_upcast = false;
+        for attribute in attributes {
+            match attribute.name.to_string().as_str() {
+                "methods" if !attribute.inner && attribute.args.is_none() => is_extended = true,
+                "upcast" if !attribute.inner && attribute.args.is_none() => is_upcast = true,
+                _ => return Err(SyntaxError {
+                    message: "expected `#[methods]` or `#[upcast]`".to_string(),
+                    span: attribute.span,
+                })
+            }
+        }
+
+        if role_traits.len() == MAX_DISPATCHED_ARGS {
+            return Err(syntax(iter.next().unwrap(), "too many dispatched types"));
+        }
+        require_keyword(&mut iter, "type", Span::call_site())?;
+        require_keyword(&mut iter, &role_name(role_traits.len()), Span::call_site())?;
+        require_punct(&mut iter, ':', Span::call_site())?;
+        let trait_name = require_ident(&mut iter, Span::call_site())?;
+        let mut trait_bounds = TokenStream::new();
+        while !if_punct(&iter, ';')? {
+            let token = next_token(&mut iter, Span::call_site())?;
+            trait_bounds.extend([token]);
+        }
+        require_punct(&mut iter, ';', Span::call_site())?;
+
+        //An enum can't have supertraits
+        if backend == Backend::Enum && !trait_bounds.is_empty() {
+            return Err(SyntaxError {
+                message: "the enum backend doesn't support trait bounds".to_string(),
+                span: trait_name.span(),
+            });
+        }
+
+        //The bounds of an extended trait are declared with the trait, and the enum backend replaces the traits
+        if is_extended && !trait_bounds.is_empty() {
+            return Err(SyntaxError {
+                message: "the bounds of a `#[methods]` trait must be declared with the trait".to_string(),
+                span: trait_name.span(),
+            });
+        }
+        if is_extended && backend == Backend::Enum {
+            return Err(SyntaxError {
+                message: "the enum backend doesn't support `#[methods]` traits".to_string(),
+                span: trait_name.span(),
+            });
+        }
+        if is_upcast && backend == Backend::Enum {
+            return Err(SyntaxError {
+                message: "the enum backend doesn't support `#[upcast]` traits".to_string(),
+                span: trait_name.span(),
+            });
+        }
+
+        //If the same trait is used for more than one arg, the bounds from the first declaration take precedence
+        let role_idx = role_traits.len();
+        match traits.iter().position(|dyn_trait| dyn_trait.name == trait_name) {
+            Some(trait_idx) => {
+                traits[trait_idx].roles.push(role_idx);
+                traits[trait_idx].is_extended |= is_extended;
+                traits[trait_idx].is_upcast |= is_upcast;
+                role_traits.push(trait_idx);
+            },
+            None => {
+                traits.push(DynTrait {
+                    name: trait_name,
+                    bounds: trait_bounds,
+                    roles: vec![role_idx],
+                    is_extended,
+                    is_upcast,
+                });
+                role_traits.push(traits.len() - 1);
+            }
+        }
+    }
+
+    //An extensible family only dispatches pairs, and its traits are implemented outside the invocation
+    if is_extensible && role_traits.len() != 2 {
+        return Err(SyntaxError {
+            message: "`#![extensible]` requires exactly two dispatched types".to_string(),
+            span: extensible_span,
+        });
+    }
+    if is_extensible && traits.iter().any(|dyn_trait| dyn_trait.is_extended) {
+        return Err(SyntaxError {
+            message: "`#![extensible]` doesn't support `#[methods]` traits".to_string(),
+            span: extensible_span,
+        });
+    }
+
+    //Parse any named groups of types, e.g. "group Ints = [i8, i16, i32];", which may be used in place of a type
+    // list in the impl blocks, or to define other groups, e.g. "group Nums = Ints + [f32, f64];"
+    let mut groups: TypeGroups = HashMap::new();
+    while if_keyword(&mut iter, "group")? {
+        require_keyword(&mut iter, "group", Span::call_site())?;
+        let group_name = require_ident(&mut iter, Span::call_site())?;
+        require_punct(&mut iter, '=', group_name.span())?;
+        let group_types = match require_type_or_type_list(&mut iter, &groups, group_name.span())? {
+            Some(group_types) => group_types,
+            None => return Err(SyntaxError {
+                message: "a group can't contain a wildcard".to_string(),
+                span: group_name.span(),
+            }),
+        };
+        require_punct(&mut iter, ';', group_name.span())?;
+
+        if groups.insert(group_name.to_string(), group_types).is_some() {
+            return Err(SyntaxError {
+                message: "duplicate group name".to_string(),
+                span: group_name.span(),
+            });
+        }
+    }
+
+    //The pub qualifiers must match across every function signature
+    let mut pub_qualifiers = TokenStream::new();
+
+    //Parse each function signature
+    let mut first_sig = true;
+    let mut fn_sigs = HashMap::new();
+    loop {
+        let mut temp_iter = iter.clone();
+
+        //If the attributes are followed by "impl", then they belong to the first impl block and we're ready to move
+        // on to implementations
+        let attributes = require_attributes(&mut temp_iter, Span::call_site())?;
+        if !fn_sigs.is_empty() && if_keyword(&mut temp_iter, "impl")? {
+            break;
+        }
+
+        match require_fn_signature(&mut temp_iter, false, Span::call_site()) {
+            Ok(sig) => {
+
+                //The supported attributes for functions are "exhaustive", "reduce", "pairs", "batch" and "static_dispatch"
+                let mut exhaustive = None;
+                let mut is_reduce = false;
+                let mut is_pairs = false;
+                let mut is_batch = false;
+                let mut is_static_dispatch = false;
+                for attribute in attributes.iter() {
+                    match attribute.name.to_string().as_str() {
+                        "exhaustive" if !attribute.inner => exhaustive = Some(parse_exhaustive_attribute(attribute)?),
+                        "reduce" if !attribute.inner && attribute.args.is_none() => is_reduce = true,
+                        "pairs" if !attribute.inner && attribute.args.is_none() => is_pairs = true,
+                        "batch" if !attribute.inner && attribute.args.is_none() => is_batch = true,
+                        "static_dispatch" if !attribute.inner && attribute.args.is_none() => is_static_dispatch = true,
+                        _ => return Err(SyntaxError {
+                            message: "expected `#[exhaustive]`, `#[reduce]`, `#[pairs]`, `#[batch]` or `#[static_dispatch]`".to_string(),
+                            span: attribute.span,
+                        })
+                    }
+                }
+
+                //The prototype either ends with a semicolon, or it has a fallback body to use for every combination
+                // of types that isn't covered by an impl block
+                let fallback_body = if if_group(&mut temp_iter, Delimiter::Brace)? {
+                    let body_group = require_group(&mut temp_iter, Delimiter::Brace, Span::call_site(), "expected fn body")?;
+                    Some(body_group.stream())
+                } else {
+                    require_punct(&mut temp_iter, ';', Span::call_site())?;
+                    None
+                };
+
+                //Check that every arg has an arg name
+                for arg in sig.args.iter() {
+                    if arg.arg_name.is_none() {
+                        return Err(SyntaxError {
+                            message: "missing arg name.  anonymous args are not allowed".to_string(),
+                            span: arg.arg_type.clone().into_iter().next().unwrap().span(),
+                        });
+                    }
+                }
+
+                //Check for duplicate function signature names
+                if fn_sigs.contains_key(&sig.fn_name.to_string()) {
+                    return Err(SyntaxError {
+                        message: "duplicate functions not allowed".to_string(),
+                        span: sig.fn_name.span(),
+                    });
+                }
+
+                //Check that the pub qualifiers match across every function signature
+                if first_sig {
+                    pub_qualifiers = sig.pub_qualifiers.clone();
+                    first_sig = false;
+                } else if tokens_to_string(pub_qualifiers.clone()) != tokens_to_string(sig.pub_qualifiers.clone()) {
+                    return Err(SyntaxError {
+                        message: "All functions must have the same visibility (e.g. 'pub')".to_string(),
+                        span: sig.fn_name.span(),
+                    });
+                }
+
+                //Identify the arg indices that might be A, B, etc.
+                let mut possible_args = vec![];
+                for (role_idx, &trait_idx) in role_traits.iter().enumerate() {
+                    let mut role_args = vec![];
+                    for (i, arg) in sig.args.iter().enumerate() {
+                        let arg_token_iter = arg.arg_type.clone().into_iter();
+                        if if_contains_sequence(&arg_token_iter, &["dyn", &traits[trait_idx].name.to_string()])? {
+                            role_args.push(i);
+                        }
+                    }
+
+                    //If we didn't identify at least one potential arg index for each dispatched type then it's an error
+                    if role_args.is_empty() {
+                        return Err(SyntaxError {
+                            message: format!("function must have at least one dyn {} argument", role_name(role_idx)),
+                            span: sig.fn_name.span(),
+                        });
+                    }
+                    possible_args.push(role_args);
+                }
+
+                //Add our valid sig to the map, and move on
+                let prototype = FnPrototype {
+                    sig,
+                    arg_idxs: vec![], //Filled in once the impls have been parsed, using possible_args
+                    arg_kinds: vec![],
+                    fallback_body,
+                    exhaustive,
+                    is_reduce,
+                    is_pairs,
+                    is_batch,
+                    is_static_dispatch,
+                };
+                fn_sigs.insert(prototype.sig.fn_name.to_string(), (prototype, possible_args));
+                iter = temp_iter;
+            },
+            Err(err) => {
+                //We need at least one function signature, and we already checked whether we were at an impl
+                return Err(err);
+            }
+        }
+    }
+
+    //Parse each type pair impl block
+    let mut impl_blocks: Vec<ImplBlock> = vec![];
+    let mut type_maps: Vec<TypeMap> = vec![HashMap::new(); role_traits.len()];
+    loop {
+        let mut impl_fns = HashMap::new();
+
+        // Check for any attributes (specifically #[commutative] and #[override])
+        let mut is_commutative = false;
+        let mut is_override = false;
+        for attribute in require_attributes(&mut iter, Span::call_site())? {
+            match attribute.name.to_string().as_str() {
+                "commutative" if !attribute.inner && attribute.args.is_none() => {
+
+                    //"commutative" is only compatible if every dispatched arg uses the same trait
+                    if traits.len() > 1 {
+                        return Err(SyntaxError {
+                            message: "commutative attribute requires the same trait for every dispatched type".to_string(),
+                            span: attribute.span,
+                        });
+                    }
+
+                    is_commutative = true;
+                },
+                "override" if !attribute.inner && attribute.args.is_none() => is_override = true,
+                _ => return Err(SyntaxError {
+                    message: "expected `#[commutative]` or `#[override]`".to_string(),
+                    span: attribute.span,
+                })
+            }
+        }
+
+        // The preamble, e.g. "impl for <TypeA, TypeB>"
+        let impl_span = iter.clone().next().map(|token| token.span()).unwrap_or_else(Span::call_site);
+        require_keyword(&mut iter, "impl", Span::call_site())?;
+        require_keyword(&mut iter, "for", Span::call_site())?;
+        let impl_types = require_impl_types(&mut iter, role_traits.len(), &groups, Span::call_site(), "expected type pair in angle brackets")?;
+        let type_lists = &impl_types.type_lists;
+
+        //"None" stands in for the type of an optional arg that is None, so it can't be combined with other types
+        let mut has_none = false;
+        for type_list in type_lists.iter().flatten() {
+            if type_list.iter().any(|type_tokens| type_string(type_tokens) == NONE_TYPE_STRING) {
+                if type_list.len() > 1 {
+                    return Err(SyntaxError {
+                        message: "`None` can't be in a list with other types".to_string(),
+                        span: impl_types.span,
+                    });
+                }
+                has_none = true;
+            }
+        }
+
+        //An optional "except" clause lists combinations of types that the block doesn't cover, e.g.
+        // "except <i128, f32>, <i128, f64>"
+        let mut exclusions = vec![];
+        if if_keyword(&mut iter, "except")? {
+            require_keyword(&mut iter, "except", Span::call_site())?;
+            loop {
+                exclusions.push(require_impl_types(&mut iter, role_traits.len(), &groups, Span::call_site(), "expected excluded types in angle brackets")?);
+                if if_punct(&iter, ',')? {
+                    require_punct(&mut iter, ',', Span::call_site())?;
+                } else {
+                    break;
+                }
+            }
+        }
+
+        // The block containing the functions
+        let fn_group = require_group(&mut iter, Delimiter::Brace, Span::call_site(), "expected curly braces for fn impls")?;
+        let mut block_token_iter = fn_group.stream().into_iter();
+        while !if_end(&block_token_iter)? {
+            let sig = require_fn_signature(&mut block_token_iter, false, fn_group.span())?;
+            let fn_body = require_group(&mut block_token_iter, Delimiter::Brace, fn_group.span(), "expected fn body")?;
+
+            //Check for duplicate function names
+            if impl_fns.contains_key(&sig.fn_name.to_string()) {
+                return Err(SyntaxError {
+                    message: "duplicate functions not allowed".to_string(),
+                    span: sig.fn_name.span(),
+                });
+            }
+
+            //Check that this implementation name matches one of the signatures defined above
+            if let Some((FnPrototype{sig: template_sig, ..}, possible_args)) = fn_sigs.get_mut(&sig.fn_name.to_string()) {
+
+                //Make sure the argument count matches the function template.  NOTE: You might think this check is unnecessary
+                // because we'd catch incompatible args later on, but we want to be able to rely on the argument list being the
+                // same length when manipulting the args array later on, before emitting the tokens to be compiled.
+                if template_sig.args.len() != sig.args.len() {
+                    return Err(SyntaxError {
+                        message: "argument count doesn't match signiture".to_string(),
+                        span: sig.fn_name.span(),
+                    });
+                }
+
+                //Make sure we can correlate the arg positions for each of the dispatched types
+                for (i, arg) in sig.args.iter().enumerate() {
+                    let arg_token_iter = arg.arg_type.clone().into_iter();
+
+                    for (role_idx, role_args) in possible_args.iter_mut().enumerate() {
+
+                        //We're looking for either a placeholder, e.g. "#A", or the concrete type itself in the case that
+                        // we only have one possible type
+                        let contains_type = match &type_lists[role_idx] {
+                            //The arg for an optional arg that is None can't be identified, so nothing is ruled out
+                            Some(type_list) if type_string(&type_list[0]) == NONE_TYPE_STRING => continue,
+                            Some(type_list) => if_contains_tokens(&arg_token_iter, type_list[0].clone().into_iter())?,
+                            None => false, //A wildcard can only be referred to with a placeholder
+                        };
+                        if !if_contains_sequence(&arg_token_iter, &["#", &role_name(role_idx)])? && !contains_type {
+                            //If this arg isn't a candidate for this role, make sure it's not in the role's list
+                            role_args.retain(|&el| el != i);
+                        }
+                    }
+                }
+
+                //If we ended up disqualifying every arg then that's a problem
+                for (role_idx, role_args) in possible_args.iter().enumerate() {
+                    if role_args.is_empty() {
+                        return Err(SyntaxError {
+                            message: format!("can't infer position of {} arg when reconciled with fn signature", role_name(role_idx)),
+                            span: sig.fn_name.span(),
+                        });
+                    }
+                }
+
+                impl_fns.insert(sig.fn_name.to_string(), (sig, fn_body));
+            } else {
+                return Err(SyntaxError {
+                    message: "matching fn signature not found".to_string(),
+                    span: sig.fn_name.span(),
+                });
+            }
+        }
+
+        //Check that every function has been implemented, except for the functions with a fallback body.  A block for
+        // an optional arg that is None only needs to implement the functions where that arg is optional, which we
+        // can't check until all the blocks have been parsed
+        for (fn_name, (prototype, _possible_args)) in fn_sigs.iter() {
+            if prototype.fallback_body.is_none() && !impl_fns.contains_key(fn_name) && !has_none {
+                return Err(SyntaxError {
+                    message: "incomplete implementation of declared functions".to_string(),
+                    span: fn_group.span(),
+                });
+            }
+        }
+
+        //Update the map of all types for each dispatched arg.  The combinations of types are resolved once all the
+        // impl blocks have been parsed, because the wildcards depend on the types from the other blocks
+        for (role_idx, type_list) in type_lists.iter().enumerate() {
+            for type_tokens in type_list.iter().flatten().filter(|type_tokens| type_string(type_tokens) != NONE_TYPE_STRING) {
+                type_maps[role_idx].insert(type_string(type_tokens), type_tokens.clone());
+            }
+        }
+        impl_blocks.push(ImplBlock {
+            span: impl_span,
+            impl_types,
+            exclusions,
+            impl_fns,
+            is_commutative,
+            is_override,
+        });
+
+        //Any more tokens must be additional impl blocks
+        if if_end(&iter)? {
+            break;
+        }
+    }
+
+    //A wildcard stands for every type used by any dispatched arg with the same trait
+    let mut wildcard_types: Vec<Vec<TokenStream>> = Vec::with_capacity(role_traits.len());
+    for &trait_idx in role_traits.iter() {
+        let mut types: Vec<(&String, &TokenStream)> = traits[trait_idx].roles.iter()
+            .flat_map(|&role_idx| type_maps[role_idx].iter())
+            .collect();
+        types.sort_by(|a, b| a.0.cmp(b.0));
+        types.dedup_by(|a, b| a.0 == b.0);
+        wildcard_types.push(types.into_iter().map(|(_, type_tokens)| type_tokens.clone()).collect());
+    }
+
+    //Go through the impl blocks from the highest precedence to the lowest, so that a combination covered by more
+    // than one block only conflicts if there isn't a single block that takes precedence over the others
+    let mut impl_blocks: Vec<(ImplSource, ImplBlock)> = impl_blocks.into_iter().map(|block| {
+        let source = ImplSource {
+            span: block.span,
+            is_override: block.is_override,
+            wildcards: block.impl_types.wildcards(),
+        };
+        (source, block)
+    }).collect();
+    impl_blocks.sort_by_key(|(source, _block)| Reverse(source.precedence()));
+
+    //Put a record in the pairs_map for each combination of types covered by each impl block
+    let mut pairs_map: HashMap<Vec<String>, FnImpls> = HashMap::new();
+    let mut pair_sources: HashMap<Vec<String>, ImplSource> = HashMap::new();
+    for (source, ImplBlock{impl_types, exclusions, impl_fns, is_commutative, ..}) in impl_blocks.into_iter() {
+
+        //Work out the combinations of types that are excluded from the block.  For a commutative block, every
+        // ordering of an excluded combination is excluded as well
+        let mut excluded_combos: Vec<(Span, HashSet<Vec<String>>, bool)> = Vec::with_capacity(exclusions.len());
+        for excluded_types in exclusions.into_iter() {
+            let excluded_span = excluded_types.span;
+            let mut combos = HashSet::new();
+            for type_combo in excluded_types.type_combos(&wildcard_types)? {
+                let type_variants = if is_commutative {
+                    permutations(&type_combo)
+                } else {
+                    vec![type_combo]
+                };
+                for types in type_variants {
+                    combos.insert(types.iter().map(type_string).collect::<Vec<String>>());
+                }
+            }
+            excluded_combos.push((excluded_span, combos, false));
+        }
+
+        let mut block_pairs = HashSet::new();
+        for type_combo in impl_types.type_combos(&wildcard_types)? {
+
+            //If the impl is_commutative, then every ordering of the types gets the implementation as well
+            let type_variants = if is_commutative {
+                permutations(&type_combo)
+            } else {
+                vec![type_combo]
+            };
+
+            for types in type_variants {
+
+                //Update the map of all types for each dispatched arg
+                let mut type_strings = Vec::with_capacity(types.len());
+                for (role_idx, type_tokens) in types.iter().enumerate() {
+                    let type_string = type_string(type_tokens);
+                    if type_string != NONE_TYPE_STRING {
+                        type_maps[role_idx].insert(type_string.clone(), type_tokens.clone());
+                    }
+                    type_strings.push(type_string);
+                }
+
+                //A commutative impl may cover the same combination more than once, which isn't a conflict
+                if !block_pairs.insert(type_strings.clone()) {
+                    continue;
+                }
+
+                //Skip the combination if the "except" clause excludes it
+                let mut is_excluded = false;
+                for (_span, combos, used) in excluded_combos.iter_mut() {
+                    if combos.contains(&type_strings) {
+                        *used = true;
+                        is_excluded = true;
+                    }
+                }
+                if is_excluded {
+                    continue;
+                }
+
+                //If a block with higher precedence already covers this combination then it wins, but if the blocks
+                // have the same precedence then one of them must be marked #[override]
+                if let Some(existing) = pair_sources.get(&type_strings) {
+                    match existing.precedence().cmp(&source.precedence()) {
+                        Ordering::Greater => continue,
+                        _ => {
+                            let type_names: Vec<String> = types.iter().map(type_display_string).collect();
+                            let type_names = type_names.join(", ");
+                            let mut error_tokens = SyntaxError {
+                                message: format!("conflicting implementations for <{}>, mark the more specific impl block with `#[override]`", type_names),
+                                span: source.span,
+                            }.into_compile_error();
+                            error_tokens.extend(SyntaxError {
+                                message: format!("other implementation for <{}> is here", type_names),
+                                span: existing.span,
+                            }.into_compile_error());
+                            return Ok(error_tokens);
+                        }
+                    }
+                }
+                pair_sources.insert(type_strings.clone(), source);
+
+                //"Self" in an implementation refers to the type of the last dispatched arg that isn't None, which is
+                // the type whose trait method the implementation is emitted into with the vtable backend
+                let self_type = types.iter().zip(type_strings.iter()).rev()
+                    .find(|(_, type_string)| *type_string != NONE_TYPE_STRING)
+                    .map(|(type_tokens, _)| type_tokens);
+
+                //Go over each fn implementation, and replace the placeholders with the concrete types
+                let mut updated_fns = HashMap::new();
+                for (fn_name, (sig, fn_body)) in impl_fns.iter() {
+
+                    //Go through the args in the function signature and swap out the #A, #B, etc. types
+                    let mut new_sig = sig.clone();
+                    for arg in new_sig.args.iter_mut() {
+                        arg.arg_type = replace_type_placeholders(arg.arg_type.clone(), &types)?;
+                    }
+                    new_sig.result = replace_type_placeholders(new_sig.result, &types)?;
+
+                    //Now do the same thing for the function body
+                    let mut new_fn_body = replace_type_placeholders(fn_body.stream(), &types)?;
+
+                    //The implementation is called as a nested fn, where "Self" isn't in scope, so it is replaced with
+                    // the concrete type
+                    if let Some(self_type) = self_type {
+                        new_fn_body = replace_self_type(new_fn_body, self_type);
+                    }
+
+                    updated_fns.insert(fn_name.clone(), (new_sig, new_fn_body));
+                }
+
+                //Put the combination in the pairs_map
+                pairs_map.insert(type_strings, updated_fns);
+            }
+        }
+
+        //An exclusion that doesn't match anything covered by the block is probably a mistake
+        for (excluded_span, _combos, used) in excluded_combos {
+            if !used {
+                return Err(SyntaxError {
+                    message: "excluded types aren't covered by the impl block".to_string(),
+                    span: excluded_span,
+                });
+            }
+        }
+    }
+
+    //For each function, collapse the possible arg positions into a single arg index for each dispatched type
+    let mut collapsed_fn_sigs: FnSigs = HashMap::with_capacity(fn_sigs.len());
+    for (fn_name, (mut prototype, mut possible_args)) in fn_sigs.into_iter() {
+
+        //If several dispatched args share the same trait, then assign the candidate positions in order, so each
+        // arg claims the first position that wasn't claimed by an earlier arg
+        for role_idx in 0..possible_args.len() {
+            for later_idx in role_idx+1..possible_args.len() {
+                if role_traits[role_idx] == role_traits[later_idx] {
+                    if let Some(&claimed) = possible_args[role_idx].first() {
+                        possible_args[later_idx].retain(|&el| el != claimed);
+                    }
+                }
+            }
+        }
+        for role_idx in (0..possible_args.len()).rev() {
+            for earlier_idx in 0..role_idx {
+                if role_traits[role_idx] == role_traits[earlier_idx] {
+                    if let Some(&claimed) = possible_args[role_idx].first() {
+                        possible_args[earlier_idx].retain(|&el| el != claimed);
+                    }
+                }
+            }
+        }
+
+        let sig = &prototype.sig;
+        let mut arg_idxs = Vec::with_capacity(possible_args.len());
+        for (role_idx, role_args) in possible_args.into_iter().enumerate() {
+
+            //If we ended up disqualifying all possible args, that's an error
+            if role_args.is_empty() {
+                return Err(SyntaxError {
+                    message: format!("can't infer position of {} arg", role_name(role_idx)),
+                    span: sig.fn_name.span(),
+                });
+            }
+
+            //Now if we have more than one index then the signature is ambiguous so that's an error
+            if role_args.len() > 1 {
+                return Err(SyntaxError {
+                    message: format!("ambiguous signature; can't infer position of {} arg", role_name(role_idx)),
+                    span: sig.fn_name.span(),
+                });
+            }
+
+            arg_idxs.push(role_args[0]);
+        }
+
+        //Work out how each dispatched arg is passed
+        let mut arg_kinds = Vec::with_capacity(arg_idxs.len());
+        for (&arg_idx, &trait_idx) in arg_idxs.iter().zip(role_traits.iter()) {
+            arg_kinds.push(dispatched_arg_kind(&sig.args[arg_idx], &traits[trait_idx].name)?);
+        }
+
+        //There is nothing to dispatch on after an arg that is None, so only the last dispatched arg may be optional
+        if arg_kinds[..arg_kinds.len() - 1].contains(&ArgKind::OptionRef) {
+            return Err(SyntaxError {
+                message: "only the last dispatched arg may be optional".to_string(),
+                span: sig.fn_name.span(),
+            });
+        }
+
+        //A "reduce" function folds a slice pairwise, so it must combine two args of the same trait into a new one
+        if prototype.is_reduce {
+            let trait_name = traits[role_traits[0]].name.to_string();
+            let result_strings: Vec<String> = sig.result.clone().into_iter().map(|token| token.to_string()).collect();
+            let result_strs: Vec<&str> = result_strings.iter().map(|result_string| result_string.as_str()).collect();
+            let is_reducible = role_traits.len() == 2 && role_traits[1] == role_traits[0] &&
+                sig.args.len() == 2 && arg_kinds.iter().all(|&arg_kind| arg_kind == ArgKind::Ref) &&
+                matches!(result_strs[..], ["Box", "<", "dyn", name, ">"] if name == trait_name);
+            if !is_reducible {
+                return Err(SyntaxError {
+                    message: format!("`#[reduce]` requires a function of the form `fn {0}(a: &dyn {1}, b: &dyn {1}) -> Box<dyn {1}>`", fn_name, trait_name),
+                    span: sig.fn_name.span(),
+                });
+            }
+        }
+
+        //The "pairs" helpers call the function for pairs of items borrowed from slices, so it can't take other args
+        if prototype.is_pairs && (role_traits.len() != 2 || sig.args.len() != 2 || arg_kinds.iter().any(|&arg_kind| arg_kind != ArgKind::Ref)) {
+            return Err(SyntaxError {
+                message: format!("`#[pairs]` requires a function of the form `fn {}(a: &dyn {}, b: &dyn {})`", fn_name, traits[role_traits[0]].name, traits[role_traits[role_traits.len() - 1]].name),
+                span: sig.fn_name.span(),
+            });
+        }
+
+        //The "batch" function takes a tuple of references for each call, so it can't take other args
+        if prototype.is_batch && (sig.args.len() != role_traits.len() || arg_kinds.iter().any(|&arg_kind| arg_kind != ArgKind::Ref)) {
+            return Err(SyntaxError {
+                message: "`#[batch]` requires a function that only takes `&dyn` dispatched args".to_string(),
+                span: sig.fn_name.span(),
+            });
+        }
+
+        //The enum backend matches on references to the enums, and the helpers that take trait objects don't apply
+        if backend == Backend::Enum {
+            if arg_kinds.iter().any(|&arg_kind| arg_kind != ArgKind::Ref && arg_kind != ArgKind::RefMut) {
+                return Err(SyntaxError {
+                    message: "the enum backend only supports `&dyn` and `&mut dyn` dispatched args".to_string(),
+                    span: sig.fn_name.span(),
+                });
+            }
+            if prototype.is_reduce || prototype.is_pairs || prototype.is_batch {
+                return Err(SyntaxError {
+                    message: "the enum backend doesn't support `#[reduce]`, `#[pairs]` or `#[batch]`".to_string(),
+                    span: sig.fn_name.span(),
+                });
+            }
+        }
+
+        //The static companion is generic over the types of the dispatched args, which must be known at compile time
+        if prototype.is_static_dispatch && (!sig.generics.is_empty() || arg_kinds.contains(&ArgKind::OptionRef)) {
+            return Err(SyntaxError {
+                message: "`#[static_dispatch]` doesn't support generic functions or optional args".to_string(),
+                span: sig.fn_name.span(),
+            });
+        }
+
+        //The args of an extensible family are downcast from trait objects
+        if is_extensible && arg_kinds.iter().any(|&arg_kind| arg_kind != ArgKind::Ref) {
+            return Err(SyntaxError {
+                message: "`#![extensible]` only supports `&dyn` dispatched args".to_string(),
+                span: sig.fn_name.span(),
+            });
+        }
+
+        //The registry is keyed by the types of a pair of args, which are downcast from trait objects, and its entries
+        // can't be generic
+        if backend == Backend::Registry {
+            if role_traits.len() != 2 || arg_kinds.iter().any(|&arg_kind| arg_kind != ArgKind::Ref) {
+                return Err(SyntaxError {
+                    message: "the registry backend requires exactly two `&dyn` dispatched args".to_string(),
+                    span: sig.fn_name.span(),
+                });
+            }
+            if !sig.generics.is_empty() || prototype.is_batch {
+                return Err(SyntaxError {
+                    message: "the registry backend doesn't support generic functions or `#[batch]`".to_string(),
+                    span: sig.fn_name.span(),
+                });
+            }
+        }
+
+        //The table holds function pointers, which can't be generic
+        if backend == Backend::Table && !sig.generics.is_empty() {
+            return Err(SyntaxError {
+                message: "the table backend doesn't support generic functions".to_string(),
+                span: sig.fn_name.span(),
+            });
+        }
+
+        prototype.arg_idxs = arg_idxs;
+        prototype.arg_kinds = arg_kinds;
+        collapsed_fn_sigs.insert(fn_name, prototype);
+    }
+    let fn_sigs = collapsed_fn_sigs;
+
+    //Check that the implementations for an arg that is None belong to functions where the arg is optional
+    for (types, fn_impls) in pairs_map.iter() {
+        for (role_idx, type_string) in types.iter().enumerate() {
+            if type_string != NONE_TYPE_STRING {
+                continue;
+            }
+            for fn_name in fn_impls.keys() {
+                if fn_sigs[fn_name].arg_kinds[role_idx] != ArgKind::OptionRef {
+                    return Err(SyntaxError {
+                        message: format!("`{}` doesn't take an optional '{}' arg, so it can't be implemented for `None`", fn_name, role_name(role_idx)),
+                        span: pair_sources[types].span,
+                    });
+                }
+            }
+        }
+    }
+
+    //==================================================================================================================
+    // PHASE 2: Build the Output Tokens
+    //==================================================================================================================
+
+    //Dispatched args that share a trait also share one list of types
+    let mut trait_types: Vec<TypeMap> = vec![HashMap::new(); traits.len()];
+    for (role_idx, type_map) in type_maps.into_iter().enumerate() {
+        trait_types[role_traits[role_idx]].extend(type_map);
+    }
+    let role_types: Vec<&TypeMap> = role_traits.iter().map(|&trait_idx| &trait_types[trait_idx]).collect();
+
+    //Each type is identified within its trait by an ordinal, which is its position in the sorted list of the trait's
+    // types
+    let trait_ordinals: Vec<HashMap<String, usize>> = trait_types.iter().map(|type_map| {
+        let mut type_strings: Vec<&String> = type_map.keys().collect();
+        type_strings.sort();
+        type_strings.into_iter().enumerate().map(|(ordinal, type_string)| (type_string.clone(), ordinal)).collect()
+    }).collect();
+
+    //Check that every combination of types is implemented for the functions that are "exhaustive".  A function
+    // with a fallback body covers every combination.  An extension only covers the combinations with its own types
+    let mut result_tokens = TokenStream::new();
+    for (fn_name, prototype) in fn_sigs.iter().filter(|_| extension.is_none()) {
+        let exhaustive = match prototype.exhaustive.or(invocation_exhaustive) {
+            Some(exhaustive) if prototype.fallback_body.is_none() => exhaustive,
+            _ => continue,
+        };
+
+        //If the last arg is optional, then None is one more type it can take
+        let mut all_types = type_prefixes(&role_types);
+        if prototype.arg_kinds.last() == Some(&ArgKind::OptionRef) {
+            for mut types in type_prefixes(&role_types[..role_types.len() - 1]) {
+                types.push(NONE_TYPE_STRING.to_string());
+                all_types.push(types);
+            }
+        }
+
+        let mut missing_types = vec![];
+        for types in all_types {
+            if !pairs_map.get(&types).is_some_and(|fn_impls| fn_impls.contains_key(fn_name)) {
+                missing_types.push(format!("<{}>", type_display_names(&types, &role_types).join(", ")));
+            }
+        }
+        if missing_types.is_empty() {
+            continue;
+        }
+        missing_types.sort();
+
+        let message = format!("`{}` is not implemented for {}", fn_name, missing_types.join(", "));
+        let span = prototype.sig.fn_name.span();
+        match exhaustive {
+            Exhaustive::Error => result_tokens.extend(SyntaxError { message, span }.into_compile_error()),
+            Exhaustive::Warn => {
+                //There is no stable way for a proc macro to emit a warning, so we use a deprecated item instead
+                result_tokens.extend(quote_spanned! {span=>
+                    const _: () = {
+                        #[deprecated(note = #message)]
+                        struct MissingImplementations;
+                        let _ = MissingImplementations;
+                    };
+                });
+            }
+        }
+    }
+
+    //Transmute all of the function prototypes into trait methods.  The trait for the first dispatched arg gets one
+    // l1 method per function, and the trait for each subsequent arg gets one method per function for every combination
+    // of the types that were dispatched before it.  With the table backend, the traits have no methods for functions
+    let mut trait_sig_tokens = vec![TokenStream::new(); traits.len()];
+    let mut ln_sigs: LnSigs = HashMap::new();
+    let dyn_type_map: TypeMap = HashMap::from([(DYN_TYPE_STRING.to_string(), {
+        let trait_name = &traits[role_traits[0]].name;
+        quote! { dyn #trait_name }
+    })]);
+    for (fn_name, prototype) in fn_sigs.iter().filter(|_| backend == Backend::Vtable) {
+        let FnPrototype{sig, arg_idxs, arg_kinds, fallback_body, ..} = prototype;
+
+        //Each method also has a "can" method that answers the "can_" predicate, unless the fallback makes it always
+        // true.  The "can" method for the A arg of an extensible family forwards to the one that takes it as a trait
+        // object, like the method itself
+        let has_can = fallback_body.is_none();
+        if is_extensible && has_can {
+            let dyn_can_sig_tokens = render_can_sig(fn_name, prototype, &[DYN_TYPE_STRING.to_string()], &role_types);
+            trait_sig_tokens[role_traits[1]].extend(quote! { #dyn_can_sig_tokens; });
+        }
+
+        //The trait for the B arg of an extensible family has another method, which takes the A arg as a trait object.
+        // The methods for the A types of the family forward to it by default, so a type added by an extension only
+        // needs to implement that method
+        let dyn_sig = if is_extensible {
+            let dyn_prefix = vec![DYN_TYPE_STRING.to_string()];
+            let (dyn_sig, _old_self_arg) = transmute_to_ln_signature(sig.clone(), &dyn_prefix, &[&dyn_type_map, role_types[1]], arg_idxs, arg_kinds)?;
+            let dyn_sig_tokens = render_fn_signature(dyn_sig.clone())?;
+            trait_sig_tokens[role_traits[1]].extend(quote! { #dyn_sig_tokens; });
+            ln_sigs.insert((fn_name.clone(), dyn_prefix), (dyn_sig.clone(), dyn_sig_tokens));
+            Some(dyn_sig)
+        } else {
+            None
+        };
+
+        for (role_idx, &trait_idx) in role_traits.iter().enumerate() {
+            for prefix in type_prefixes(&role_types[..role_idx]) {
+
+                let (new_sig, _old_self_arg) = transmute_to_ln_signature(sig.clone(), &prefix, &role_types, arg_idxs, arg_kinds)?;
+                let sig_tokens = render_fn_signature(new_sig.clone())?;
+                trait_sig_tokens[trait_idx].extend(sig_tokens.clone());
+                match &dyn_sig {
+                    Some(dyn_sig) if role_idx == 1 => {
+                        let dyn_fn_name = &dyn_sig.fn_name;
+                        let arg_names = new_sig.args.iter().filter_map(|arg| arg.arg_name.clone());
+                        trait_sig_tokens[trait_idx].extend(quote! {
+                            {
+                                self.#dyn_fn_name(#(#arg_names),*)
+                            }
+                        });
+                    },
+                    _ => trait_sig_tokens[trait_idx].extend(quote! { ; }),
+                }
+
+                if has_can {
+                    let can_sig_tokens = render_can_sig(fn_name, prototype, &prefix, &role_types);
+                    trait_sig_tokens[trait_idx].extend(can_sig_tokens);
+                    if is_extensible && role_idx == 1 {
+                        let dyn_can_fn_name = can_method_name(fn_name, &[DYN_TYPE_STRING.to_string()], sig.fn_name.span());
+                        let a_arg_name = sig.args[arg_idxs[0]].arg_name.clone().unwrap();
+                        trait_sig_tokens[trait_idx].extend(quote! {
+                            {
+                                self.#dyn_can_fn_name(#a_arg_name)
+                            }
+                        });
+                    } else {
+                        trait_sig_tokens[trait_idx].extend(quote! { ; });
+                    }
+                }
+                ln_sigs.insert((fn_name.clone(), prefix), (new_sig, sig_tokens));
+            }
+        }
+    }
+
+    //An extension only implements the traits of the family for its own types
+    if let Some(extension) = &extension {
+        return render_extension(extension, &traits, &trait_types, &role_types, &pairs_map, &pair_sources, &fn_sigs, &ln_sigs);
+    }
+
+    //The trait of a "reduce" function can box a copy of an item, which "_fold" returns for a slice with one item
+    let reduce_trait_idx = fn_sigs.values().find(|prototype| prototype.is_reduce).map(|_| role_traits[0]);
+
+    //The table backend and the "batch" functions recover the concrete types from the ordinals, so the ordinal is
+    // declared by an unsafe supertrait, which can't be implemented without an "unsafe impl"
+    let needs_ordinal = backend == Backend::Table || fn_sigs.values().any(|prototype| prototype.is_batch);
+
+    // --1-- Create the definition of the traits, or the enums that take their place
+    for (trait_idx, (dyn_trait, sig_tokens)) in traits.iter().zip(trait_sig_tokens.iter()).enumerate().filter(|_| backend != Backend::Enum) {
+        let trait_name = &dyn_trait.name;
+        let ordinal_trait_name = ordinal_trait_name(trait_name);
+        let trait_bounds = match (needs_ordinal, dyn_trait.bounds.is_empty()) {
+            (false, _) => dyn_trait.bounds.clone(),
+            (true, true) => quote! { : #ordinal_trait_name },
+            (true, false) => {
+                let bounds = &dyn_trait.bounds;
+                quote! { #bounds + #ordinal_trait_name }
+            },
+        };
+        let clone_box_sig_tokens = if reduce_trait_idx == Some(trait_idx) {
+            quote! {
+                fn double_dyn_clone_box(&self) -> Box<dyn #trait_name>;
+            }
+        } else {
+            TokenStream::new()
+        };
+        let any_sig_tokens = if is_extensible {
+            quote! {
+                fn double_dyn_as_any(&self) -> &dyn ::std::any::Any;
+
+                fn double_dyn_type_path(&self) -> &'static str;
+            }
+        } else {
+            TokenStream::new()
+        };
+        let method_tokens = quote! {
+            fn double_dyn_type_name(&self) -> &'static str;
+
+            #any_sig_tokens
+
+            #clone_box_sig_tokens
+
+            #sig_tokens
+        };
+
+        //With the registry backend, types outside the invocation may implement the trait, so it only requires the
+        // method to downcast it, and the type name defaults to the type path
+        let method_tokens = if backend == Backend::Registry {
+            quote! {
+                fn double_dyn_as_any(&self) -> &dyn ::std::any::Any;
+
+                fn double_dyn_type_name(&self) -> &'static str {
+                    ::std::any::type_name::<Self>()
+                }
+
+                #clone_box_sig_tokens
+            }
+        } else {
+            method_tokens
+        };
+
+        if needs_ordinal {
+            result_tokens.extend(quote! {
+                /// Declares the ordinal of a type, which is its position in the sorted list of the trait's types
+                ///
+                /// # Safety
+                ///
+                /// The dispatch recovers the concrete type from the ordinal, so a type must return its own ordinal,
+                /// or an ordinal that is out of range for all of the trait's types
+                #[allow(clippy::missing_safety_doc)]
+                #pub_qualifiers unsafe trait #ordinal_trait_name {
+                    fn double_dyn_type_ordinal(&self) -> usize;
+                }
+            });
+        }
+
+        //An extended trait is defined by the callback that the "methods" attribute left in its place, which adds the
+        // ordinal trait as a supertrait
+        if dyn_trait.is_extended {
+            let macro_name = methods_macro_name(trait_name, None);
+            let supertrait_tokens = if needs_ordinal {
+                quote! { where Self: #ordinal_trait_name; }
+            } else {
+                TokenStream::new()
+            };
+            result_tokens.extend(quote! {
+                #macro_name!{ #supertrait_tokens #method_tokens }
+            });
+        } else {
+            result_tokens.extend(quote! {
+                #[allow(clippy::ptr_arg)]
+                #pub_qualifiers trait #trait_name #trait_bounds {
+                    #method_tokens
+                }
+            });
+        }
+
+        //The bridge trait is implemented for every sized type that implements the trait, so a trait that extends the
+        // bridge trait can be upcast in generic code, where a "dyn" can't be coerced
+        if dyn_trait.is_upcast {
+            let bridge_name = Ident::new(&format!("As{}", trait_name), trait_name.span());
+            let as_name = Ident::new(&format!("as_{}", AsSnakeCase(trait_name.to_string())), trait_name.span());
+            let as_mut_name = Ident::new(&format!("as_{}_mut", AsSnakeCase(trait_name.to_string())), trait_name.span());
+            result_tokens.extend(quote! {
+                #pub_qualifiers trait #bridge_name: #trait_name {
+                    fn #as_name(&self) -> &dyn #trait_name;
+
+                    fn #as_mut_name(&mut self) -> &mut dyn #trait_name;
+                }
+
+                impl<T: #trait_name> #bridge_name for T {
+                    fn #as_name(&self) -> &dyn #trait_name {
+                        self
+                    }
+
+                    fn #as_mut_name(&mut self) -> &mut dyn #trait_name {
+                        self
+                    }
+                }
+            });
+        }
+    }
+
+    if backend == Backend::Enum {
+        for (trait_idx, dyn_trait) in traits.iter().enumerate() {
+            result_tokens.extend(render_enum(&dyn_trait.name, &trait_types[trait_idx], &trait_ordinals[trait_idx], &pub_qualifiers));
+        }
+    }
+
+    // --2-- Emit the trait impls for every type.  If a trait is used for more than one dispatched arg, the impl
+    // includes the methods for all of those args
+    for (trait_idx, (dyn_trait, type_map)) in traits.iter().zip(trait_types.iter()).enumerate().filter(|_| backend != Backend::Enum) {
+        for (type_name, type_tokens) in type_map.iter() {
+
+            let mut method_impls = TokenStream::new();
+            for &role_idx in dyn_trait.roles.iter().filter(|_| backend == Backend::Vtable) {
+                for prefix in type_prefixes(&role_types[..role_idx]) {
+                    let impl_tokens = if role_idx + 1 < role_traits.len() {
+                        render_forwarding_fns(&prefix, type_name, &pairs_map, &role_types, &fn_sigs, &ln_sigs)?
+                    } else {
+                        let mut types = prefix;
+                        types.push(type_name.clone());
+                        render_ln_fns_for_types(&types, &pairs_map, &role_types, &fn_sigs, &ln_sigs)?
+                    };
+                    method_impls.extend(impl_tokens);
+                }
+            }
+
+            if reduce_trait_idx == Some(trait_idx) {
+                method_impls.extend(render_clone_box_fn(&dyn_trait.name));
+            }
+
+            //An extensible family can only identify an A type it doesn't know about by its type path
+            if is_extensible {
+                method_impls.extend(render_any_fns());
+                if dyn_trait.roles.contains(&1) {
+                    method_impls.extend(render_dyn_ln_fns(&[], type_name, &pairs_map, &role_types, &fn_sigs, &ln_sigs)?);
+                }
+            }
+
+            let trait_name = &dyn_trait.name;
+            let type_display_name = type_display_string(type_tokens);
+            let method_tokens = quote! {
+                fn double_dyn_type_name(&self) -> &'static str {
+                    #type_display_name
+                }
+
+                #method_impls
+            };
+            let method_tokens = if backend == Backend::Registry {
+                quote! {
+                    fn double_dyn_as_any(&self) -> &dyn ::std::any::Any {
+                        self
+                    }
+
+                    #method_impls
+                }
+            } else {
+                method_tokens
+            };
+
+            //The impls of an extended trait are also defined by callbacks.  Substituting the placeholders often leads
+            // to casts like "i32 as i32", so we allow them.  The signatures of the implementations also follow the
+            // prototype, which may take a Box
+            if dyn_trait.is_extended {
+                let macro_name = methods_macro_name(trait_name, Some(type_tokens));
+                result_tokens.extend(quote! {
+                    #macro_name!{ #method_tokens }
+                });
+            } else {
+                result_tokens.extend(quote! {
+                    #[allow(clippy::ptr_arg, clippy::unnecessary_cast, clippy::boxed_local)]
+                    impl #trait_name for #type_tokens {
+                        #method_tokens
+                    }
+                });
+            }
+
+            if needs_ordinal {
+                let type_ordinal = trait_ordinals[trait_idx][type_name];
+                result_tokens.extend(render_ordinal_impl(trait_name, type_tokens, quote! { #type_ordinal }));
+            }
+        }
+    }
+
+    // --3-- Emit the top-level function(s)
+    for (orig_fn_name, prototype) in fn_sigs.iter() {
+        let FnPrototype{sig, arg_idxs, ..} = prototype;
+
+        let sig_tokens = render_fn_signature(sig.clone())?;
+
+        //The plain function panics if the types aren't implemented, and the "try_" variant returns the error
+        let mut try_sig = sig.clone();
+        try_sig.fn_name = Ident::new(&format!("try_{}", orig_fn_name), sig.fn_name.span());
+        try_sig.result = dispatch_result_type(&sig.result);
+        let try_sig_tokens = render_fn_signature(try_sig.clone())?;
+
+        //With the table and enum backends, the dispatch is rendered once in the "try_" variant, and the plain function
+        // calls it.  With the enum backend, the dispatched args are references to the enums
+        if backend != Backend::Vtable {
+            let (sig_tokens, try_sig_tokens, dispatch_tokens) = if backend == Backend::Table {
+                (sig_tokens, try_sig_tokens, render_table_dispatch(orig_fn_name, prototype, &pairs_map, &role_traits, &role_types, &trait_ordinals)?)
+            } else if backend == Backend::Registry {
+                result_tokens.extend(render_registry(orig_fn_name, prototype, &pairs_map, &role_traits, &traits, &role_types, &pub_qualifiers)?);
+                (sig_tokens, try_sig_tokens, render_registry_dispatch(orig_fn_name, prototype)?)
+            } else {
+                let mut enum_sig = sig.clone();
+                let mut enum_try_sig = try_sig.clone();
+                for (&arg_idx, &trait_idx) in arg_idxs.iter().zip(role_traits.iter()) {
+                    let enum_arg_type = replace_dyn_trait(&sig.args[arg_idx].arg_type, &traits[trait_idx].name);
+                    enum_sig.args[arg_idx].arg_type = enum_arg_type.clone();
+                    enum_try_sig.args[arg_idx].arg_type = enum_arg_type;
+                }
+                (render_fn_signature(enum_sig)?, render_fn_signature(enum_try_sig)?, render_enum_dispatch(orig_fn_name, prototype, &pairs_map, &role_traits, &traits, &role_types)?)
+            };
+            let try_fn_name = &try_sig.fn_name;
+            let arg_names: Vec<Ident> = sig.args.iter().map(|arg| arg.arg_name.clone().unwrap()).collect();
+            result_tokens.extend(quote! {
+                #sig_tokens {
+                    match #try_fn_name(#(#arg_names),*) {
+                        Ok(result) => result,
+                        Err(err) => panic!("{}", err),
+                    }
+                }
+
+                #[allow(unreachable_patterns, clippy::ptr_arg, clippy::unnecessary_cast, clippy::boxed_local)]
+                #try_sig_tokens {
+                    #dispatch_tokens
+                }
+            });
+            continue;
+        }
+
+        let (l1_sig, _l1_sig_tokens) = ln_sigs.get(&(orig_fn_name.clone(), vec![])).unwrap();
+        let l1_fn_name = l1_sig.fn_name.clone();
+        result_tokens.extend(render_fallback_fn(orig_fn_name, prototype)?);
+
+        //Get the name of the A arg, so we can use it to call the l1 trait method
+        let a_arg_name = sig.args[arg_idxs[0]].arg_name.clone().unwrap();
+
+        //We'll pass all of the other args to the l1 method
+        let mut other_arg_name_tokens = TokenStream::new();
+        for (i, arg) in sig.args.iter().enumerate() {
+            if i != arg_idxs[0] {
+                let arg_name = arg.arg_name.clone().unwrap();
+                other_arg_name_tokens.extend(quote! {
+                    #arg_name,
+                });
+            }
+        }
+
+        let fn_tokens = quote! {
+            #sig_tokens {
+                match #a_arg_name.#l1_fn_name(#other_arg_name_tokens) {
+                    Ok(result) => result,
+                    Err(err) => panic!("{}", err),
+                }
+            }
+
+            #try_sig_tokens {
+                #a_arg_name.#l1_fn_name(#other_arg_name_tokens)
+            }
+        };
+
+        result_tokens.extend(fn_tokens);
+    }
+
+    // --4-- Emit the table of every implemented combination of types, sorted so the order is stable
+    let mut impl_infos = vec![];
+    for (types, fn_impls) in pairs_map.iter() {
+        let type_names = type_display_names(types, &role_types);
+        for fn_name in fn_impls.keys() {
+            impl_infos.push((fn_name.clone(), type_names.clone()));
+        }
+    }
+    impl_infos.sort();
+
+    let mut impl_info_tokens = TokenStream::new();
+    for (fn_name, type_names) in impl_infos.iter() {
+        impl_info_tokens.extend(quote! {
+            ::double_dyn::ImplInfo {
+                fn_name: #fn_name,
+                type_names: &[#(#type_names),*],
+            },
+        });
+    }
+    let table_name = Ident::new(&format!("{}_IMPLS", AsShoutySnakeCase(traits[0].name.to_string())), traits[0].name.span());
+    result_tokens.extend(quote! {
+        #pub_qualifiers const #table_name: &[::double_dyn::ImplInfo] = &[
+            #impl_info_tokens
+        ];
+    });
+
+    // --5-- Emit a "can_" predicate for each function, to check whether a combination of types is supported.  The
+    // answer for each combination is known at compile time, so it is looked up the same way as the implementation
+    for (orig_fn_name, prototype) in fn_sigs.iter() {
+        let FnPrototype{sig, arg_idxs, arg_kinds, fallback_body, ..} = prototype;
+
+        let can_fn_name = Ident::new(&format!("can_{}", orig_fn_name), sig.fn_name.span());
+        let mut arg_tokens = TokenStream::new();
+        let mut arg_name_tokens = TokenStream::new();
+        for ((&arg_idx, &trait_idx), arg_kind) in arg_idxs.iter().zip(role_traits.iter()).zip(arg_kinds.iter()) {
+            let arg_name = sig.args[arg_idx].arg_name.clone().unwrap();
+            let trait_name = &traits[trait_idx].name;
+            arg_name_tokens.extend(quote! { #arg_name, });
+            if backend == Backend::Enum {
+                arg_tokens.extend(quote! { #arg_name: &#trait_name, });
+            } else {
+                let arg_type = can_arg_type(&sig.args[arg_idx].arg_type, *arg_kind);
+                arg_tokens.extend(quote! { #arg_name: #arg_type, });
+            }
+        }
+
+        //Every combination is supported if the function has a fallback
+        let body_tokens = if fallback_body.is_some() {
+            quote! {
+                let _ = (#arg_name_tokens);
+                true
+            }
+        } else if backend == Backend::Registry {
+            //The registry may have entries that were registered at runtime, so it has to be asked
+            let static_name = Ident::new(&format!("{}", AsShoutySnakeCase(orig_fn_name)), sig.fn_name.span());
+            let dispatched_names = arg_idxs.iter().map(|&arg_idx| sig.args[arg_idx].arg_name.clone().unwrap());
+            quote! {
+                #static_name.contains((#(#dispatched_names.double_dyn_as_any().type_id()),*))
+            }
+        } else if backend == Backend::Table {
+            //A table of flags, indexed like the table of implementations
+            let (dims, _ordinal_exprs, index_tokens) = render_table_index(prototype, &role_types);
+            let mut flags = vec![false; dims.iter().product()];
+            for types in pairs_map.keys().filter(|types| is_implemented(orig_fn_name, types, &pairs_map)) {
+                flags[table_entry_index(types, &dims, &role_traits, &role_types, &trait_ordinals)] = true;
+            }
+            let flag_count = flags.len();
+            quote! {
+                static __DOUBLE_DYN_IMPLEMENTED: [bool; #flag_count] = [#(#flags),*];
+                #index_tokens.is_some_and(|index| __DOUBLE_DYN_IMPLEMENTED[index])
+            }
+        } else if backend == Backend::Enum {
+            //A match arm for every implemented combination of variants
+            let mut match_arm_tokens = TokenStream::new();
+            for types in pairs_map.keys().filter(|types| is_implemented(orig_fn_name, types, &pairs_map)) {
+                let mut pattern_tokens = TokenStream::new();
+                for (role_idx, type_string) in types.iter().enumerate() {
+                    let enum_name = &traits[role_traits[role_idx]].name;
+                    let variant_name = enum_variant_name(&role_types[role_idx][type_string], enum_name.span());
+                    pattern_tokens.extend(quote! { #enum_name::#variant_name(_), });
+                }
+                match_arm_tokens.extend(quote! {
+                    (#pattern_tokens) => true,
+                });
+            }
+            quote! {
+                match (#arg_name_tokens) {
+                    #match_arm_tokens
+                    _ => false,
+                }
+            }
+        } else {
+            //The "can" methods follow the same chain of trait methods as the dispatch
+            let l1_can_fn_name = can_method_name(orig_fn_name, &[], sig.fn_name.span());
+            let a_arg_name = sig.args[arg_idxs[0]].arg_name.clone().unwrap();
+            let other_arg_names = arg_idxs[1..].iter().map(|&arg_idx| sig.args[arg_idx].arg_name.clone().unwrap());
+            quote! {
+                #a_arg_name.#l1_can_fn_name(#(#other_arg_names),*)
+            }
+        };
+
+        result_tokens.extend(quote! {
+            #pub_qualifiers fn #can_fn_name(#arg_tokens) -> bool {
+                #body_tokens
+            }
+        });
+    }
+
+    // --6-- Emit a "_fold" function for each "reduce" function, to combine every item in a slice from left to right
+    for (orig_fn_name, FnPrototype{sig, is_reduce, ..}) in fn_sigs.iter() {
+        if !is_reduce {
+            continue;
+        }
+
+        let fold_fn_name = Ident::new(&format!("{}_fold", orig_fn_name), sig.fn_name.span());
+        let try_fn_name = Ident::new(&format!("try_{}", orig_fn_name), sig.fn_name.span());
+        let trait_name = &traits[role_traits[0]].name;
+        result_tokens.extend(quote! {
+            #pub_qualifiers fn #fold_fn_name(items: &[&dyn #trait_name]) -> ::std::result::Result<Box<dyn #trait_name>, ::double_dyn::FoldError> {
+                match items {
+                    [] => Err(::double_dyn::FoldError::EmptySlice {
+                        fn_name: #orig_fn_name,
+                    }),
+                    [only] => Ok(only.double_dyn_clone_box()),
+                    [first, second, rest @ ..] => {
+                        let mut acc = #try_fn_name(*first, *second)?;
+                        for item in rest {
+                            acc = #try_fn_name(&*acc, *item)?;
+                        }
+                        Ok(acc)
+                    },
+                }
+            }
+        });
+    }
+
+    // --7-- Emit the pairwise helpers for each "pairs" function, which call the function for every pair of items
+    // and skip the pairs that aren't implemented.  The "can_" predicate decides which pairs are skipped, so skipping
+    // a pair doesn't build an error
+    for (orig_fn_name, FnPrototype{sig, arg_idxs, is_pairs, ..}) in fn_sigs.iter() {
+        if !is_pairs {
+            continue;
+        }
+
+        let sig_fn_name = &sig.fn_name;
+        let can_fn_name = Ident::new(&format!("can_{}", orig_fn_name), sig.fn_name.span());
+        let result = if sig.result.is_empty() {
+            quote! { () }
+        } else {
+            sig.result.clone()
+        };
+
+        //The args are passed in the order of the prototype, which may not be the order of the dispatched args
+        let (a_item, b_item) = (quote! { &**a_item }, quote! { &**b_item });
+        let call_arg_tokens = if arg_idxs[0] == 0 {
+            quote! { #a_item, #b_item }
+        } else {
+            quote! { #b_item, #a_item }
+        };
+
+        //Every pair within one slice only makes sense if both args use the same trait
+        if role_traits[0] == role_traits[1] {
+            let all_pairs_fn_name = Ident::new(&format!("{}_all_pairs", orig_fn_name), sig.fn_name.span());
+            let trait_name = &traits[role_traits[0]].name;
+            result_tokens.extend(quote! {
+                #pub_qualifiers fn #all_pairs_fn_name(items: &[Box<dyn #trait_name>], mut f: impl FnMut(usize, usize, #result)) {
+                    for (a_idx, a_item) in items.iter().enumerate() {
+                        for (b_idx, b_item) in items.iter().enumerate().skip(a_idx + 1) {
+                            if #can_fn_name(#a_item, #b_item) {
+                                f(a_idx, b_idx, #sig_fn_name(#call_arg_tokens));
+                            }
+                        }
+                    }
+                }
+            });
+        }
+
+        let bipartite_fn_name = Ident::new(&format!("{}_bipartite_pairs", orig_fn_name), sig.fn_name.span());
+        let a_trait_name = &traits[role_traits[0]].name;
+        let b_trait_name = &traits[role_traits[1]].name;
+        result_tokens.extend(quote! {
+            #pub_qualifiers fn #bipartite_fn_name(a_items: &[Box<dyn #a_trait_name>], b_items: &[Box<dyn #b_trait_name>], mut f: impl FnMut(usize, usize, #result)) {
+                for (a_idx, a_item) in a_items.iter().enumerate() {
+                    for (b_idx, b_item) in b_items.iter().enumerate() {
+                        if #can_fn_name(#a_item, #b_item) {
+                            f(a_idx, b_idx, #sig_fn_name(#call_arg_tokens));
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    // --8-- Emit a "_batch" function for each "batch" function, which sorts the calls by the ordinals of their types,
+    // and then runs the implementation for each combination of types over all of its calls in one loop
+    for (orig_fn_name, FnPrototype{sig, arg_idxs, is_batch, ..}) in fn_sigs.iter() {
+        if !is_batch {
+            continue;
+        }
+
+        let batch_fn_name = Ident::new(&format!("{}_batch", orig_fn_name), sig.fn_name.span());
+        let sig_fn_name = &sig.fn_name;
+        let result = if sig.result.is_empty() {
+            quote! { () }
+        } else {
+            sig.result.clone()
+        };
+
+        //Each call is a tuple with the dispatched args in the order of the roles, but the args are passed in the
+        // order of the prototype
+        let mut item_type_tokens = TokenStream::new();
+        let mut key_tokens = TokenStream::new();
+        let mut plain_args = vec![TokenStream::new(); arg_idxs.len()];
+        for (role_idx, &trait_idx) in role_traits.iter().enumerate() {
+            let trait_name = &traits[trait_idx].name;
+            let field = Literal::usize_unsuffixed(role_idx);
+            item_type_tokens.extend(quote! { &dyn #trait_name, });
+            key_tokens.extend(quote! { item.#field.double_dyn_type_ordinal(), });
+            plain_args[arg_idxs[role_idx]] = quote! { items[idx].#field };
+        }
+
+        //Each implemented combination of types gets a match arm that runs the implementation over the calls
+        let mut match_arm_tokens = TokenStream::new();
+        for (types, fn_impls) in pairs_map.iter() {
+            let (pair_fn_sig, pair_fn_body) = match fn_impls.get(orig_fn_name) {
+                Some(pair_fn) => pair_fn,
+                None => continue,
+            };
+
+            let impl_fn_name = implementation_fn_name(pair_fn_sig.fn_name.span());
+            let mut impl_sig = pair_fn_sig.clone();
+            impl_sig.pub_qualifiers = TokenStream::new();
+            impl_sig.fn_name = impl_fn_name.clone();
+            let impl_sig_tokens = render_fn_signature(impl_sig)?;
+
+            let mut ordinal_tokens = TokenStream::new();
+            let mut impl_args = vec![TokenStream::new(); arg_idxs.len()];
+            for (role_idx, type_string) in types.iter().enumerate() {
+                let ordinal = trait_ordinals[role_traits[role_idx]][type_string];
+                let type_tokens = role_types[role_idx].get(type_string).unwrap();
+                let field = Literal::usize_unsuffixed(role_idx);
+                ordinal_tokens.extend(quote! { #ordinal, });
+
+                //SAFETY: The ordinal trait is unsafe to implement, and its impls promise that the ordinal identifies
+                // the concrete type behind the trait object, so the cast is valid
+                impl_args[arg_idxs[role_idx]] = quote! {
+                    unsafe { &*(items[idx].#field as *const _ as *const #type_tokens) }
+                };
+            }
+
+            match_arm_tokens.extend(quote! {
+                (#ordinal_tokens) => {
+                    #impl_sig_tokens {
+                        #pair_fn_body
+                    }
+
+                    for &idx in run {
+                        results[idx] = Some(#impl_fn_name(#(#impl_args),*));
+                    }
+                },
+            });
+        }
+
+        result_tokens.extend(quote! {
+            #[allow(clippy::ptr_arg, clippy::unnecessary_cast)]
+            #pub_qualifiers fn #batch_fn_name(items: &[(#item_type_tokens)]) -> Vec<#result> {
+                let keys: Vec<_> = items.iter().map(|item| (#key_tokens)).collect();
+                let mut order: Vec<usize> = (0..items.len()).collect();
+                order.sort_by_key(|&idx| keys[idx]);
+
+                let mut results: Vec<Option<#result>> = (0..items.len()).map(|_| None).collect();
+                for run in order.chunk_by(|&idx, &next_idx| keys[idx] == keys[next_idx]) {
+                    match keys[run[0]] {
+                        #match_arm_tokens
+                        _ => {
+                            for &idx in run {
+                                results[idx] = Some(#sig_fn_name(#(#plain_args),*));
+                            }
+                        },
+                    }
+                }
+                results.into_iter().map(|result| result.unwrap()).collect()
+            }
+        });
+    }
+
+    // --9-- Emit the static companion for each "static_dispatch" function.  Each function gets a trait that is generic
+    // over the types of the args after A, and is implemented for the types of A, so the implementation is resolved at
+    // compile time
+    for (orig_fn_name, FnPrototype{sig, arg_idxs, arg_kinds, is_static_dispatch, ..}) in fn_sigs.iter() {
+        if !is_static_dispatch {
+            continue;
+        }
+
+        let static_trait_name = Ident::new(&format!("{}With", AsUpperCamelCase(orig_fn_name)), sig.fn_name.span());
+        let static_fn_name = Ident::new(&format!("{}_static", orig_fn_name), sig.fn_name.span());
+        let role_params: Vec<Ident> = (0..arg_idxs.len()).map(|role_idx| Ident::new(&format!("__DoubleDyn{}", role_name(role_idx)), sig.fn_name.span())).collect();
+        let other_role_params = &role_params[1..];
+
+        //Renders the signature of the static function, given the tokens for the type of each dispatched arg
+        let render_static_sig = |generics: TokenStream, role_type_tokens: &[TokenStream]| {
+            let mut static_sig = sig.clone();
+            static_sig.pub_qualifiers = TokenStream::new();
+            static_sig.fn_name = static_fn_name.clone();
+            static_sig.generics = generics;
+            for (role_idx, &arg_idx) in arg_idxs.iter().enumerate() {
+                static_sig.args[arg_idx].arg_type = arg_kinds[role_idx].concrete_type(&role_type_tokens[role_idx]);
+                static_sig.args[arg_idx].is_mut = false;
+            }
+            static_sig
+        };
+
+        //The trait method takes A as Self
+        let mut trait_role_types = vec![quote! { Self }];
+        trait_role_types.extend(other_role_params.iter().map(|param| quote! { #param }));
+        let trait_sig_tokens = render_fn_signature(render_static_sig(TokenStream::new(), &trait_role_types))?;
+        result_tokens.extend(quote! {
+            #pub_qualifiers trait #static_trait_name<#(#other_role_params),*> {
+                #trait_sig_tokens;
+            }
+        });
+
+        //Each implemented combination of types gets an impl of the trait that calls the implementation
+        for (types, fn_impls) in pairs_map.iter() {
+            let (pair_fn_sig, pair_fn_body) = match fn_impls.get(orig_fn_name) {
+                Some(pair_fn) => pair_fn,
+                None => continue,
+            };
+
+            let type_tokens: Vec<TokenStream> = types.iter().enumerate().map(|(role_idx, type_string)| role_types[role_idx][type_string].clone()).collect();
+            let method_sig_tokens = render_fn_signature(render_static_sig(TokenStream::new(), &type_tokens))?;
+
+            let impl_fn_name = implementation_fn_name(pair_fn_sig.fn_name.span());
+            let mut impl_sig = pair_fn_sig.clone();
+            impl_sig.pub_qualifiers = TokenStream::new();
+            impl_sig.fn_name = impl_fn_name.clone();
+            let impl_sig_tokens = render_fn_signature(impl_sig)?;
+
+            //A boxed arg is unboxed if the implementation takes it by value
+            let mut arg_name_tokens = TokenStream::new();
+            for (i, arg) in sig.args.iter().enumerate() {
+                let arg_name = arg.arg_name.clone().unwrap();
+                let is_unboxed = arg_idxs.iter().position(|&arg_idx| arg_idx == i)
+                    .is_some_and(|role_idx| arg_kinds[role_idx] == ArgKind::Boxed && !is_boxed_type(&pair_fn_sig.args[i].arg_type));
+                if is_unboxed {
+                    arg_name_tokens.extend(quote! { *#arg_name, });
+                } else {
+                    arg_name_tokens.extend(quote! { #arg_name, });
+                }
+            }
+
+            let self_type = &type_tokens[0];
+            let other_types = &type_tokens[1..];
+            result_tokens.extend(quote! {
+                #[allow(clippy::ptr_arg, clippy::unnecessary_cast, clippy::boxed_local)]
+                impl #static_trait_name<#(#other_types),*> for #self_type {
+                    #method_sig_tokens {
+                        #impl_sig_tokens {
+                            #pair_fn_body
+                        }
+
+                        #impl_fn_name(#arg_name_tokens)
+                    }
+                }
+            });
+        }
+
+        //The free function is generic over the type of every dispatched arg
+        let a_param = &role_params[0];
+        let generics = quote! { #a_param: #static_trait_name<#(#other_role_params),*>, #(#other_role_params),* };
+        let role_param_tokens: Vec<TokenStream> = role_params.iter().map(|param| quote! { #param }).collect();
+        let mut fn_sig = render_static_sig(generics, &role_param_tokens);
+        fn_sig.pub_qualifiers = pub_qualifiers.clone();
+        let fn_sig_tokens = render_fn_signature(fn_sig)?;
+        let arg_names: Vec<Ident> = sig.args.iter().map(|arg| arg.arg_name.clone().unwrap()).collect();
+        result_tokens.extend(quote! {
+            #fn_sig_tokens {
+                #a_param::#static_fn_name(#(#arg_names),*)
+            }
+        });
+    }
+
+    Ok(result_tokens)
+}
+
+//Returns the names of a combination of types, as they are written in the impl blocks
+fn type_display_names(types: &[String], role_types: &[&TypeMap]) -> Vec<String> {
+    types.iter().zip(role_types.iter())
+        .map(|(type_string, type_map)| if type_string == NONE_TYPE_STRING {
+            "None".to_string()
+        } else {
+            type_display_string(type_map.get(type_string).unwrap())
+        })
+        .collect()
+}
+
+//Implements the "methods" attribute, which replaces a trait, or an impl of it, with a macro_rules callback that
+// emits the trait or impl along with the methods passed to it by a "double_dyn!" invocation
+fn methods_internal(args: TokenStream, input: TokenStream) -> Result<TokenStream, SyntaxError> {
+    require_end(&mut args.into_iter())?;
+    let is_ident = |token: &TokenTree, keyword: &str| matches!(token, TokenTree::Ident(ident) if ident == keyword);
+
+    //The item is everything up to the body, which is the last token, e.g. "pub trait Shape: Debug"
+    let mut tokens: Vec<TokenTree> = input.into_iter().collect();
+    let body = match tokens.pop() {
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => group.stream(),
+        _ => return Err(SyntaxError {
+            message: "expected a trait or an impl of a trait".to_string(),
+            span: Span::call_site(),
+        }),
+    };
+    let keyword_idx = match tokens.iter().position(|token| is_ident(token, "trait") || is_ident(token, "impl")) {
+        Some(idx) => idx,
+        None => return Err(SyntaxError {
+            message: "expected a trait or an impl of a trait".to_string(),
+            span: Span::call_site(),
+        }),
+    };
+
+    //The name of the callback depends on whether the item is a trait, or an impl of a trait for a type.  The lints
+    // allowed on an impl match the impls emitted by "double_dyn!" for the generated methods
+    let keyword = &tokens[keyword_idx];
+    let (macro_name, allow_tokens) = if is_ident(keyword, "trait") {
+        match tokens.get(keyword_idx + 1) {
+            Some(TokenTree::Ident(trait_name)) => (methods_macro_name(trait_name, None), quote!{ #[allow(clippy::ptr_arg)] }),
+            _ => return Err(syntax(keyword, "expected trait name")),
+        }
+    } else {
+        match (tokens.get(keyword_idx + 1), tokens.get(keyword_idx + 2)) {
+            (Some(TokenTree::Ident(trait_name)), Some(for_token)) if is_ident(for_token, "for") => {
+                let type_tokens: TokenStream = tokens[keyword_idx + 3..].iter().cloned().collect();
+                (methods_macro_name(trait_name, Some(&type_tokens)),
+                    quote!{ #[allow(clippy::ptr_arg, clippy::unnecessary_cast, clippy::boxed_local)] })
+            },
+            _ => return Err(syntax(keyword, "expected `impl Trait for Type`, without generics or a path")),
+        }
+    };
+
+    //The callback for a trait may be passed a supertrait to add, which goes in the where clause, so it doesn't have to
+    // be merged with the bounds
+    let supertrait_arm_tokens = if is_ident(keyword, "trait") {
+        let where_tokens = match tokens.iter().position(|token| is_ident(token, "where")) {
+            None => quote! { where },
+            Some(_) if matches!(tokens.last(), Some(TokenTree::Punct(punct)) if punct.as_char() == ',') => TokenStream::new(),
+            Some(_) => quote! { , },
+        };
+        let item_tokens: TokenStream = tokens.iter().cloned().collect();
+        quote! {
+            (where Self: $supertrait:path; $($methods:tt)*) => {
+                #allow_tokens
+                #item_tokens #where_tokens Self: $supertrait {
+                    #body
+
+                    $($methods)*
+                }
+            };
+        }
+    } else {
+        TokenStream::new()
+    };
+
+    let item_tokens: TokenStream = tokens.into_iter().collect();
+    Ok(quote! {
+        macro_rules! #macro_name {
+            #supertrait_arm_tokens
+            ($($methods:tt)*) => {
+                #allow_tokens
+                #item_tokens {
+                    #body
+
+                    $($methods)*
+                }
+            };
+        }
+    })
+}
+
+//Implements "double_dyn_decl!", which begins with the name of the callback, e.g. "macro factors;", followed by the
+// declarations that are passed through to "double_dyn!" along with the impl blocks from "double_dyn_impl!".  The
+// callback may be marked "#[macro_export]", so other crates can extend the family
+fn double_dyn_decl_internal(input: TokenStream) -> Result<TokenStream, SyntaxError> {
+    let mut iter = input.into_iter();
+    let mut is_exported = false;
+    for attribute in require_attributes(&mut iter, Span::call_site())? {
+        match attribute.name.to_string().as_str() {
+            "macro_export" if !attribute.inner && attribute.args.is_none() => is_exported = true,
+            _ => return Err(SyntaxError {
+                message: "expected `#[macro_export]`".to_string(),
+                span: attribute.span,
+            })
+        }
+    }
+    require_keyword(&mut iter, "macro", Span::call_site())?;
+    let macro_name = require_ident(&mut iter, Span::call_site())?;
+    require_punct(&mut iter, ';', macro_name.span())?;
+    let decl_tokens: TokenStream = iter.collect();
+
+    //An "extend" attribute must come before the attributes in the declarations.  The "use" makes a callback that
+    // isn't exported nameable by path from the other modules of the crate
+    let (export_tokens, use_tokens) = if is_exported {
+        (quote! { #[macro_export] }, TokenStream::new())
+    } else {
+        (TokenStream::new(), quote! {
+            #[allow(unused_imports)]
+            pub(crate) use #macro_name;
+        })
+    };
+    Ok(quote! {
+        #export_tokens
+        macro_rules! #macro_name {
+            (#![extend($($extend:tt)*)] $($impls:tt)*) => {
+                ::double_dyn::double_dyn!{
+                    #![extend($($extend)*)]
+                    #decl_tokens
+
+                    $($impls)*
+                }
+            };
+            ($($impls:tt)*) => {
+                ::double_dyn::double_dyn!{
+                    #decl_tokens
+
+                    $($impls)*
+                }
+            };
+        }
+
+        #use_tokens
+    })
+}
+
+//Implements "double_dyn_impl!", which begins with the path to a callback emitted by "double_dyn_decl!", e.g.
+// "crate::ops::factors;", followed by the impl blocks.  The path may also be to a macro emitted by a
+// "double_dyn_impl!" with a "collect" attribute, which passes on the impl blocks it carries along with these ones
+fn double_dyn_impl_internal(input: TokenStream) -> Result<TokenStream, SyntaxError> {
+    let mut iter = input.into_iter();
+    let mut macro_path = TokenStream::new();
+    while !if_punct(&iter, ';')? {
+        let token = next_token(&mut iter, Span::call_site())?;
+        macro_path.extend([token]);
+    }
+    if macro_path.is_empty() {
+        return Err(syntax(iter.next().unwrap(), "expected the path to a `double_dyn_decl!` macro"));
+    }
+    require_punct(&mut iter, ';', Span::call_site())?;
+
+    //A "collect" attribute must come before an "extend" attribute, which is carried along with the impl blocks
+    let mut collect_name = None;
+    if if_inner_attribute(&iter)? {
+        let mut peek_iter = iter.clone();
+        let attribute = require_attribute(&mut peek_iter, Span::call_site())?;
+        if attribute.name == "collect" {
+            let args_group = attribute.args.ok_or_else(|| SyntaxError {
+                message: "expected `#![collect(name)]`".to_string(),
+                span: attribute.span,
+            })?;
+            let mut args_iter = args_group.stream().into_iter();
+            collect_name = Some(require_ident(&mut args_iter, args_group.span())?);
+            require_end(&mut args_iter)?;
+            iter = peek_iter;
+        }
+    }
+    let impl_tokens: TokenStream = iter.collect();
+
+    let collect_name = match collect_name {
+        Some(collect_name) => collect_name,
+        None => return Ok(quote! {
+            #macro_path!{ #impl_tokens }
+        }),
+    };
+
+    //The collecting macro is invoked from another module, so the path must be absolute, and a path from the crate
+    // root is resolved from the crate where the macro is defined
+    let mut path_iter = macro_path.clone().into_iter();
+    let first_token = path_iter.next().unwrap();
+    let macro_path = match &first_token {
+        TokenTree::Ident(ident) if ident == "crate" => {
+            let dollar = Punct::new('$', Spacing::Alone);
+            let rest: TokenStream = path_iter.collect();
+            quote! { #dollar #ident #rest }
+        },
+        TokenTree::Ident(ident) if ident != "self" && ident != "super" && path_iter.next().is_some() => macro_path,
+        TokenTree::Punct(punct) if punct.as_char() == ':' => macro_path,
+        _ => return Err(syntax(first_token, "a `double_dyn_impl!` with `#![collect]` needs an absolute path, e.g. `crate::ops::factors`")),
+    };
+    let dollar = Punct::new('$', Spacing::Alone);
+    Ok(quote! {
+        macro_rules! #collect_name {
+            (#dollar(#dollar impls:tt)*) => {
+                ::double_dyn::double_dyn_impl!{
+                    #macro_path;
+                    #impl_tokens
+
+                    #dollar(#dollar impls)*
+                }
+            };
+        }
+
+        #[allow(unused_imports)]
+        pub(crate) use #collect_name;
+    })
+}
+
+//Returns the name of the callback left by the "methods" attribute in place of a trait, or an impl of the trait for
+// a type
+fn methods_macro_name(trait_name: &Ident, type_tokens: Option<&TokenStream>) -> Ident {
+    let macro_name = match type_tokens {
+        None => format!("__double_dyn_trait_{}", trait_name),
+        Some(type_tokens) => format!("__double_dyn_impl_{}_{}", trait_name, type_string(type_tokens)),
+    };
+    Ident::new(&macro_name, Span::call_site())
+}
+
+//Parses the args of an "exhaustive" attribute, which are either empty or "warn"
+fn parse_exhaustive_attribute(attribute: &Attribute) -> Result<Exhaustive, SyntaxError> {
+    match &attribute.args {
+        None => Ok(Exhaustive::Error),
+        Some(args_group) => {
+            let mut args_iter = args_group.stream().into_iter();
+            require_keyword(&mut args_iter, "warn", args_group.span())?;
+            require_end(&mut args_iter)?;
+            Ok(Exhaustive::Warn)
+        }
+    }
+}
+
+//Parses the args of a "backend" attribute, which are "vtable", "table", "enum" or "registry"
+fn parse_backend_attribute(attribute: &Attribute) -> Result<Backend, SyntaxError> {
+    let err = || SyntaxError {
+        message: "expected `#![backend(vtable)]`, `#![backend(table)]`, `#![backend(enum)]` or `#![backend(registry)]`".to_string(),
+        span: attribute.span,
+    };
+    let args_group = attribute.args.as_ref().ok_or_else(err)?;
+    let mut args_iter = args_group.stream().into_iter();
+    let backend = match require_ident(&mut args_iter, args_group.span())?.to_string().as_str() {
+        "vtable" => Backend::Vtable,
+        "table" => Backend::Table,
+        "enum" => Backend::Enum,
+        "registry" => Backend::Registry,
+        _ => return Err(err()),
+    };
+    require_end(&mut args_iter)?;
+    Ok(backend)
+}
+
+//Parses the args of an "extend" attribute, which are the path to the module of the family, followed by the list of
+// types to add to it, e.g. "#![extend(shapes::family, [Hexagon, Star])]"
+fn parse_extend_attribute(attribute: &Attribute) -> Result<Extension, SyntaxError> {
+    let err = || SyntaxError {
+        message: "expected `#![extend(path, [Type, ..])]`".to_string(),
+        span: attribute.span,
+    };
+    let args_group = attribute.args.as_ref().ok_or_else(err)?;
+    let mut args_iter = args_group.stream().into_iter();
+    let mut path = TokenStream::new();
+    while !if_punct(&args_iter, ',')? {
+        let token = next_token(&mut args_iter, args_group.span())?;
+        path.extend([token]);
+    }
+    require_punct(&mut args_iter, ',', args_group.span())?;
+    let types = match require_type_or_type_list(&mut args_iter, &HashMap::new(), args_group.span())? {
+        Some(types) if !path.is_empty() => types,
+        _ => return Err(err()),
+    };
+    require_end(&mut args_iter)?;
+    Ok(Extension { path, types })
+}
+
+//Returns the letter used to refer to a dispatched arg, e.g. "A" for the first arg, "B" for the second, etc.
+fn role_name(role_idx: usize) -> String {
+    ((b'A' + role_idx as u8) as char).to_string()
+}
+
+//Wraps the result type of a function, to make the type returned by the trait methods and the "try_" variants
+fn dispatch_result_type(result: &TokenStream) -> TokenStream {
+    let result = if result.is_empty() {
+        quote! { () }
+    } else {
+        result.clone()
+    };
+    quote! { ::std::result::Result<#result, ::double_dyn::DispatchError> }
+}
+
+//Returns the snake_case string used to identify a type, e.g. in method names and as a key in the type maps
+fn type_string(type_tokens: &TokenStream) -> String {
+    format!("{}", AsSnakeCase(tokens_to_string(type_tokens.clone())))
+}
+
+//Returns every combination that takes one element from each list, in order
+fn cartesian_product<T: Clone>(lists: &[Vec<T>]) -> Vec<Vec<T>> {
+    let mut combos = vec![vec![]];
+    for list in lists {
+        combos = combos.into_iter().flat_map(|combo: Vec<T>| {
+            list.iter().map(move |item| {
+                let mut new_combo = combo.clone();
+                new_combo.push(item.clone());
+                new_combo
+            })
+        }).collect();
+    }
+    combos
+}
+
+//Returns every ordering of the items
+fn permutations<T: Clone>(items: &[T]) -> Vec<Vec<T>> {
+    if items.len() < 2 {
+        return vec![items.to_vec()];
+    }
+    let mut result = vec![];
+    for i in 0..items.len() {
+        let mut rest = items.to_vec();
+        let item = rest.remove(i);
+        for mut perm in permutations(&rest) {
+            perm.insert(0, item.clone());
+            result.push(perm);
+        }
+    }
+    result
+}
+
+//Returns every combination of type names for the dispatched args whose types are provided
+fn type_prefixes(role_types: &[&TypeMap]) -> Vec<Vec<String>> {
+    let type_name_lists: Vec<Vec<String>> = role_types.iter().map(|type_map| type_map.keys().cloned().collect()).collect();
+    cartesian_product(&type_name_lists)
+}
+
+//Parse the types in an impl block header, e.g. "<TypeA, [TypeB, TypeC]>", with one type or type list for each
+// dispatched arg.  The lists may be zipped together rather than combined, e.g. "zip <[u8, u16], [u16, u32]>", and
+// the type of an arg may be bound to a name and repeated for a later arg, e.g. "<T in [i32, f64], T>"
+fn require_impl_types(iter: &mut TokenIter, role_count: usize, groups: &TypeGroups, err_span: Span, err_msg: &str) -> Result<ImplTypes, SyntaxError> {
+
+    let is_zipped = if if_keyword(iter, "zip")? {
+        require_keyword(iter, "zip", err_span)?;
+        true
+    } else {
+        false
+    };
+
+    let angle_group = require_angle_group(iter, err_span, err_msg)?;
+    let span = angle_group.open_bracket.span();
+    let close_span = angle_group.close_bracket.span();
+    let mut token_iter = angle_group.interior_tokens.into_iter();
+
+    //We support either a type by itself, a list of types in square brackets, a group, or a "_" wildcard, for each
+    // dispatched arg
+    let mut type_lists: Vec<Option<Vec<TokenStream>>> = vec![];
+    let mut bound_roles = vec![];
+    let mut bindings: HashMap<String, usize> = HashMap::new();
+    for role_idx in 0..role_count {
+        if role_idx > 0 {
+            if !if_punct(&token_iter, ',')? { //So the error message is a little better
+                return Err(SyntaxError {
+                    message: format!("expected type or type list for '{}'", role_name(role_idx)),
+                    span: close_span,
+                });
+            }
+            require_punct(&mut token_iter, ',', close_span)?;
+        }
+
+        //See if the arg repeats the type of an earlier arg that was bound to a name
+        let mut peek_iter = token_iter.clone();
+        let bound_role = match (peek_iter.next(), peek_iter.next()) {
+            (Some(TokenTree::Ident(ident)), None) => bindings.get(&ident.to_string()).copied(),
+            (Some(TokenTree::Ident(ident)), Some(TokenTree::Punct(punct))) if punct.as_char() == ',' => bindings.get(&ident.to_string()).copied(),
+            _ => None,
+        };
+        if let Some(bound_role) = bound_role {
+            token_iter.next();
+            type_lists.push(type_lists[bound_role].clone());
+            bound_roles.push(Some(bound_role));
+            continue;
+        }
+
+        //See if the arg's type is bound to a name, e.g. "T in [i32, f64]"
+        let mut peek_iter = token_iter.clone();
+        if let (Some(TokenTree::Ident(binding_name)), Some(TokenTree::Ident(in_keyword))) = (peek_iter.next(), peek_iter.next()) {
+            if in_keyword == "in" {
+                token_iter = peek_iter;
+                if bindings.insert(binding_name.to_string(), role_idx).is_some() {
+                    return Err(syntax(TokenTree::Ident(binding_name), "duplicate name in impl types"));
+                }
+            }
+        }
+
+        type_lists.push(require_type_or_type_list(&mut token_iter, groups, close_span)?);
+        bound_roles.push(None);
+    }
+    if if_punct(&token_iter, ',')? {
+        require_punct(&mut token_iter, ',', close_span)?;
+    }
+    require_end(&mut token_iter)?;
+
+    Ok(ImplTypes {
+        span,
+        type_lists,
+        bound_roles,
+        is_zipped,
+    })
+}
+
+impl ImplTypes {
+
+    //Returns the number of "_" wildcards, not counting the args that repeat the type of another arg
+    fn wildcards(&self) -> usize {
+        self.type_lists.iter().zip(self.bound_roles.iter())
+            .filter(|(type_list, bound_role)| type_list.is_none() && bound_role.is_none())
+            .count()
+    }
+
+    //Returns every combination of types covered, once the wildcards have been replaced with every type for the
+    // dispatched arg
+    fn type_combos(self, wildcard_types: &[Vec<TokenStream>]) -> Result<Vec<Vec<TokenStream>>, SyntaxError> {
+        let type_lists = resolve_wildcards(self.type_lists, wildcard_types, self.span)?;
+
+        //Only the args that don't repeat another arg's type are combined
+        let free_lists: Vec<Vec<TokenStream>> = type_lists.into_iter().zip(self.bound_roles.iter())
+            .filter(|(_type_list, bound_role)| bound_role.is_none())
+            .map(|(type_list, _bound_role)| type_list)
+            .collect();
+        let free_combos = if self.is_zipped {
+            let zip_len = free_lists[0].len();
+            if free_lists.iter().any(|type_list| type_list.len() != zip_len) {
+                return Err(SyntaxError {
+                    message: "zipped type lists must have the same length".to_string(),
+                    span: self.span,
+                });
+            }
+            (0..zip_len).map(|i| free_lists.iter().map(|type_list| type_list[i].clone()).collect()).collect()
+        } else {
+            cartesian_product(&free_lists)
+        };
+
+        //Fill in the args that repeat the type of another arg
+        let mut combos = Vec::with_capacity(free_combos.len());
+        for free_combo in free_combos {
+            let mut free_iter = free_combo.into_iter();
+            let mut combo: Vec<TokenStream> = Vec::with_capacity(self.bound_roles.len());
+            for bound_role in self.bound_roles.iter() {
+                match bound_role {
+                    Some(bound_role) => combo.push(combo[*bound_role].clone()),
+                    None => combo.push(free_iter.next().unwrap()),
+                }
+            }
+            combos.push(combo);
+        }
+        Ok(combos)
+    }
+}
+
+//Replaces each "_" wildcard with every type for the dispatched arg
+fn resolve_wildcards(type_lists: Vec<Option<Vec<TokenStream>>>, wildcard_types: &[Vec<TokenStream>], err_span: Span) -> Result<Vec<Vec<TokenStream>>, SyntaxError> {
+    let mut resolved_type_lists = Vec::with_capacity(type_lists.len());
+    for (role_idx, type_list) in type_lists.into_iter().enumerate() {
+        match type_list {
+            Some(type_list) => resolved_type_lists.push(type_list),
+            None => {
+                if wildcard_types[role_idx].is_empty() {
+                    return Err(SyntaxError {
+                        message: format!("wildcard for '{}' doesn't match any types", role_name(role_idx)),
+                        span: err_span,
+                    });
+                }
+                resolved_type_lists.push(wildcard_types[role_idx].clone());
+            }
+        }
+    }
+    Ok(resolved_type_lists)
+}
+
+//Parse a type by itself, a list of types in square brackets, a group name, or a "_" wildcard, which is returned
+// as None.  Lists and groups may be combined with "+", e.g. "Ints + [f32, f64]"
+fn require_type_or_type_list(iter: &mut TokenIter, groups: &TypeGroups, err_span: Span) -> Result<Option<Vec<TokenStream>>, SyntaxError> {
+
+    //A "_" wildcard is resolved into a list of types after all the impl blocks have been parsed
+    if if_keyword(iter, "_")? {
+        require_keyword(iter, "_", err_span)?;
+        return Ok(None);
+    }
+
+    let mut type_list = vec![];
+    loop {
+        if if_group(iter, Delimiter::Bracket)? {
+            let type_list_group = require_group(iter, Delimiter::Bracket, err_span, "expected square braces for type array")?;
+            let mut type_tokens_iter = type_list_group.stream().into_iter();
+            loop {
+                match if_type_group(&type_tokens_iter, groups) {
+                    Some(group_types) => {
+                        type_list.extend(group_types.iter().cloned());
+                        type_tokens_iter.next();
+                    },
+                    None => type_list.push(require_type(&mut type_tokens_iter, type_list_group.span())?),
+                }
+                if if_end(&type_tokens_iter)? {
+                    break;
+                } else {
+                    require_punct(&mut type_tokens_iter, ',', type_list_group.span())?;
+                }
+            }
+            if type_list.is_empty() {
+                //return err if we didn't push anything to the array
+                return Err(syntax(TokenTree::Group(type_list_group), "expected at least one type"));
+            }
+        } else if let Some(group_types) = if_type_group(iter, groups) {
+            type_list.extend(group_types.iter().cloned());
+            iter.next();
+        } else {
+            //A type by itself can't be combined with "+", because the "+" would be parsed as part of the type
+            let type_group = require_type(iter, err_span)?;
+            type_list.push(type_group);
+            break;
+        }
+
+        if if_punct(iter, '+')? {
+            require_punct(iter, '+', err_span)?;
+        } else {
+            break;
+        }
+    }
+
+    //Combining lists and groups may mention the same type more than once
+    let mut type_strings = HashSet::new();
+    type_list.retain(|type_tokens| type_strings.insert(type_string(type_tokens)));
+
+    Ok(Some(type_list))
+}
+
+//Checks whether the next token is the name of a group, rather than the start of a type, and if so returns the
+// types in the group
+fn if_type_group<'a>(iter: &TokenIter, groups: &'a TypeGroups) -> Option<&'a Vec<TokenStream>> {
+    let mut peek_iter = iter.clone();
+    let group_types = match peek_iter.next() {
+        Some(TokenTree::Ident(ident)) => groups.get(&ident.to_string())?,
+        _ => return None,
+    };
+    match peek_iter.next() {
+        None => Some(group_types),
+        Some(TokenTree::Punct(punct)) if matches!(punct.as_char(), ',' | ';' | '+') => Some(group_types),
+        _ => None,
+    }
+}
+
+//Renders the methods that dispatch on self, and then call the method for the next dispatched arg.  If the next arg
+// is optional then the None case is handled here, as if "None" were the type of the next arg
+fn render_forwarding_fns(
+    prefix: &[String],
+    self_type_name: &str,
+    pairs_map: &HashMap<Vec<String>, FnImpls>,
+    role_types: &[&TypeMap],
+    fn_sigs: &FnSigs,
+    ln_sigs: &LnSigs) -> Result<TokenStream, SyntaxError> {
+
+    let role_idx = prefix.len();
+    let mut ln_impls = TokenStream::new();
+    for (orig_fn_name, prototype) in fn_sigs.iter() {
+        let FnPrototype{sig, arg_idxs, arg_kinds, ..} = prototype;
+
+        let (_ln_sig, ln_sig_tokens) = ln_sigs.get(&(orig_fn_name.clone(), prefix.to_vec())).unwrap();
+
+        //Get the name of the next arg, so we can use it to call the next method
+        let next_arg_name = sig.args[arg_idxs[role_idx + 1]].arg_name.clone().unwrap();
+
+        //We'll pass all of the args that haven't been dispatched yet to the next method, followed by the args that
+        // have been dispatched, including self
+        let mut other_arg_name_tokens = TokenStream::new();
+        for (i, arg) in sig.args.iter().enumerate() {
+            if !arg_idxs[..=role_idx + 1].contains(&i) {
+                let arg_name = arg.arg_name.clone().unwrap();
+                other_arg_name_tokens.extend(quote! {
+                    #arg_name,
+                });
+            }
+        }
+        for &arg_idx in arg_idxs[..role_idx].iter() {
+            let arg_name = sig.args[arg_idx].arg_name.clone().unwrap();
+            other_arg_name_tokens.extend(quote! {
+                #arg_name,
+            });
+        }
+
+        //Figure out the next function name
+        let mut next_prefix = prefix.to_vec();
+        next_prefix.push(self_type_name.to_string());
+        let (next_sig, _next_sig_tokens) = ln_sigs.get(&(orig_fn_name.clone(), next_prefix.clone())).unwrap();
+        let next_fn_name = &next_sig.fn_name;
+
+        //Compose a function that calls the appropriate next function with the right args
+        let next_call_tokens = quote! {
+            #next_arg_name.#next_fn_name(#other_arg_name_tokens self)
+        };
+        let body_tokens = if arg_kinds[role_idx + 1] == ArgKind::OptionRef {
+            let mut none_types = next_prefix.clone();
+            none_types.push(NONE_TYPE_STRING.to_string());
+            let none_tokens = match render_dispatched_call(orig_fn_name, prototype, &none_types, role_idx, pairs_map)? {
+                Some(call_tokens) => call_tokens,
+                None => render_dispatch_error(orig_fn_name, &none_types, role_idx, role_types),
+            };
+            quote! {
+                match #next_arg_name {
+                    Some(#next_arg_name) => #next_call_tokens,
+                    None => {
+                        #none_tokens
+                    }
+                }
+            }
+        } else {
+            next_call_tokens
+        };
+
+        ln_impls.extend(quote! {
+            #ln_sig_tokens {
+                #body_tokens
+            }
+        });
+
+        //The "can" method calls the next "can" method with the same args, except that every arg is a reference
+        if prototype.fallback_body.is_none() {
+            let can_sig_tokens = render_can_sig(orig_fn_name, prototype, prefix, role_types);
+            let next_can_fn_name = can_method_name(orig_fn_name, &next_prefix, sig.fn_name.span());
+            let can_arg_names = arg_idxs[role_idx + 2..].iter().chain(arg_idxs[..role_idx].iter()).map(|&arg_idx| sig.args[arg_idx].arg_name.clone().unwrap());
+            let next_can_call_tokens = quote! {
+                #next_arg_name.#next_can_fn_name(#(#can_arg_names,)* self)
+            };
+            let can_body_tokens = if arg_kinds[role_idx + 1] == ArgKind::OptionRef {
+                let mut none_types = next_prefix;
+                none_types.push(NONE_TYPE_STRING.to_string());
+                let is_none_implemented = is_implemented(orig_fn_name, &none_types, pairs_map);
+                quote! {
+                    match #next_arg_name {
+                        Some(#next_arg_name) => #next_can_call_tokens,
+                        None => #is_none_implemented,
+                    }
+                }
+            } else {
+                next_can_call_tokens
+            };
+            ln_impls.extend(quote! {
+                #can_sig_tokens {
+                    #can_body_tokens
+                }
+            });
+        }
+    }
+
+    Ok(ln_impls)
+}
+
+//Renders the methods for the last dispatched arg, given the concrete types of every dispatched arg
+fn render_ln_fns_for_types(
+    types: &[String],
+    pairs_map: &HashMap<Vec<String>, FnImpls>,
+    role_types: &[&TypeMap],
+    fn_sigs: &FnSigs,
+    ln_sigs: &LnSigs) -> Result<TokenStream, SyntaxError> {
+
+    let prefix = &types[..types.len() - 1];
+    let self_role_idx = types.len() - 1;
+    let mut ln_impls = TokenStream::new();
+
+    for (orig_fn_name, prototype) in fn_sigs.iter() {
+
+        let (ln_sig, ln_sig_tokens) = ln_sigs.get(&(orig_fn_name.clone(), prefix.to_vec())).unwrap();
+
+        match render_dispatched_call(orig_fn_name, prototype, types, self_role_idx, pairs_map)? {
+            Some(call_tokens) => {
+                ln_impls.extend(quote! {
+                    #ln_sig_tokens {
+                        #call_tokens
+                    }
+                });
+            },
+            None => {
+
+                //Emit a method that returns an error naming the concrete types.  Prepend a '_' to the arg names
+                // in order to supress "unused variable" warnings
+                let mut new_sig = ln_sig.clone();
+                for arg in new_sig.args.iter_mut() {
+                    if let Some(arg_name) = &mut arg.arg_name {
+                        *arg_name = Ident::new(&format!("_{}", arg_name), arg_name.span());
+                    }
+                }
+                let new_sig_tokens = render_fn_signature(new_sig)?;
+                let error_tokens = render_dispatch_error(orig_fn_name, types, self_role_idx, role_types);
+
+                ln_impls.extend(quote! {
+                    #new_sig_tokens {
+                        #error_tokens
+                    }
+                });
+            }
+        }
+
+        //Every arg has been dispatched, so the "can" method knows the answer.  It doesn't need the args before self
+        if prototype.fallback_body.is_none() {
+            let can_sig_tokens = render_can_sig(orig_fn_name, prototype, prefix, role_types);
+            let is_types_implemented = is_implemented(orig_fn_name, types, pairs_map);
+            ln_impls.extend(quote! {
+                #[allow(unused_variables)]
+                #can_sig_tokens {
+                    #is_types_implemented
+                }
+            });
+        }
+    }
+
+    Ok(ln_impls)
+}
+
+//Renders the methods that let an extensible family downcast the args, and name their types
+fn render_any_fns() -> TokenStream {
+    quote! {
+        fn double_dyn_as_any(&self) -> &dyn ::std::any::Any {
+            self
+        }
+
+        fn double_dyn_type_path(&self) -> &'static str {
+            ::std::any::type_name::<Self>()
+        }
+    }
+}
+
+//Returns the name of the unsafe supertrait that declares the ordinal of a type
+fn ordinal_trait_name(trait_name: &Ident) -> Ident {
+    Ident::new(&format!("{}Ordinal", trait_name), trait_name.span())
+}
+
+//Renders the impl of the ordinal trait for a type.  The ordinal must identify the type among the types of the trait,
+// or be out of range for all of them, because the concrete type is recovered from it by an unchecked cast
+fn render_ordinal_impl(trait_name: &Ident, type_tokens: &TokenStream, ordinal_tokens: TokenStream) -> TokenStream {
+    let ordinal_trait_name = ordinal_trait_name(trait_name);
+    quote! {
+        unsafe impl #ordinal_trait_name for #type_tokens {
+            fn double_dyn_type_ordinal(&self) -> usize {
+                #ordinal_tokens
+            }
+        }
+    }
+}
+
+//Renders the method that boxes a copy of an item, for the trait of a "reduce" function
+fn render_clone_box_fn(trait_name: &Ident) -> TokenStream {
+    quote! {
+        fn double_dyn_clone_box(&self) -> Box<dyn #trait_name> {
+            Box::new(::std::clone::Clone::clone(self))
+        }
+    }
+}
+
+//Renders the l1 methods of an extensible family for an A type added by an extension.  The B arg is downcast to each
+// of the B types that the A type is implemented with, and if none of them match, the B arg is dispatched with the A
+// arg as a trait object
+fn render_dyn_l1_fns(
+    self_type_name: &str,
+    b_types: &[String],
+    pairs_map: &HashMap<Vec<String>, FnImpls>,
+    role_types: &[&TypeMap],
+    fn_sigs: &FnSigs,
+    ln_sigs: &LnSigs) -> Result<TokenStream, SyntaxError> {
+
+    let mut l1_impls = TokenStream::new();
+    for (orig_fn_name, prototype) in fn_sigs.iter() {
+        let FnPrototype{sig, arg_idxs, ..} = prototype;
+
+        let (_l1_sig, l1_sig_tokens) = ln_sigs.get(&(orig_fn_name.clone(), vec![])).unwrap();
+        let (dyn_sig, _dyn_sig_tokens) = ln_sigs.get(&(orig_fn_name.clone(), vec![DYN_TYPE_STRING.to_string()])).unwrap();
+        let dyn_fn_name = &dyn_sig.fn_name;
+        let b_arg_name = sig.args[arg_idxs[1]].arg_name.clone().unwrap();
+
+        let mut downcast_tokens = TokenStream::new();
+        let mut can_downcast_tokens = TokenStream::new();
+        for b_type_name in b_types {
+            let types = vec![self_type_name.to_string(), b_type_name.clone()];
+            if !is_implemented(orig_fn_name, &types, pairs_map) {
+                continue;
+            }
+            let call_tokens = render_dispatched_call(orig_fn_name, prototype, &types, 0, pairs_map)?.unwrap();
+            let b_type_tokens = role_types[1].get(b_type_name).unwrap();
+            downcast_tokens.extend(quote! {
+                if let Some(#b_arg_name) = #b_arg_name.double_dyn_as_any().downcast_ref::<#b_type_tokens>() {
+                    return {
+                        #call_tokens
+                    };
+                }
+            });
+            can_downcast_tokens.extend(quote! {
+                if #b_arg_name.double_dyn_as_any().is::<#b_type_tokens>() {
+                    return true;
+                }
+            });
+        }
+
+        //The method for the B arg takes the args that aren't dispatched, followed by the A arg
+        let mut other_arg_name_tokens = TokenStream::new();
+        for (i, arg) in sig.args.iter().enumerate() {
+            if !arg_idxs.contains(&i) {
+                let arg_name = arg.arg_name.clone().unwrap();
+                other_arg_name_tokens.extend(quote! {
+                    #arg_name,
+                });
+            }
+        }
+
+        l1_impls.extend(quote! {
+            #l1_sig_tokens {
+                #downcast_tokens
+                #b_arg_name.#dyn_fn_name(#other_arg_name_tokens self)
+            }
+        });
+
+        //The "can" method checks the same B types, and then asks the B arg about the A arg as a trait object
+        if prototype.fallback_body.is_none() {
+            let can_sig_tokens = render_can_sig(orig_fn_name, prototype, &[], role_types);
+            let dyn_can_fn_name = can_method_name(orig_fn_name, &[DYN_TYPE_STRING.to_string()], sig.fn_name.span());
+            l1_impls.extend(quote! {
+                #can_sig_tokens {
+                    #can_downcast_tokens
+                    #b_arg_name.#dyn_can_fn_name(self)
+                }
+            });
+        }
+    }
+
+    Ok(l1_impls)
+}
+
+//Renders the methods of an extensible family that take the A arg as a trait object, for a B type.  The A arg is
+// downcast to each of the A types that the B type is implemented with, and if none of them match, the fallback is
+// called or an error is returned
+fn render_dyn_ln_fns(
+    a_types: &[String],
+    self_type_name: &str,
+    pairs_map: &HashMap<Vec<String>, FnImpls>,
+    role_types: &[&TypeMap],
+    fn_sigs: &FnSigs,
+    ln_sigs: &LnSigs) -> Result<TokenStream, SyntaxError> {
+
+    let mut ln_impls = TokenStream::new();
+    for (orig_fn_name, prototype) in fn_sigs.iter() {
+        let FnPrototype{sig, arg_idxs, ..} = prototype;
+
+        let (_dyn_sig, dyn_sig_tokens) = ln_sigs.get(&(orig_fn_name.clone(), vec![DYN_TYPE_STRING.to_string()])).unwrap();
+        let a_arg_name = sig.args[arg_idxs[0]].arg_name.clone().unwrap();
+
+        let mut downcast_tokens = TokenStream::new();
+        let mut can_downcast_tokens = TokenStream::new();
+        for a_type_name in a_types {
+            let types = vec![a_type_name.clone(), self_type_name.to_string()];
+            if !is_implemented(orig_fn_name, &types, pairs_map) {
+                continue;
+            }
+            let call_tokens = render_dispatched_call(orig_fn_name, prototype, &types, 1, pairs_map)?.unwrap();
+            let a_type_tokens = role_types[0].get(a_type_name).unwrap();
+            downcast_tokens.extend(quote! {
+                if let Some(#a_arg_name) = #a_arg_name.double_dyn_as_any().downcast_ref::<#a_type_tokens>() {
+                    return {
+                        #call_tokens
+                    };
+                }
+            });
+            can_downcast_tokens.extend(quote! {
+                if #a_arg_name.double_dyn_as_any().is::<#a_type_tokens>() {
+                    return true;
+                }
+            });
+        }
+
+        //The A type is unknown, so the error names it with the type path from the A arg
+        let dyn_types = vec![DYN_TYPE_STRING.to_string(), self_type_name.to_string()];
+        let end_tokens = match render_dispatched_call(orig_fn_name, prototype, &dyn_types, 1, pairs_map)? {
+            Some(call_tokens) => call_tokens,
+            None => quote! {
+                Err(::double_dyn::DispatchError {
+                    fn_name: #orig_fn_name,
+                    type_names: vec![#a_arg_name.double_dyn_type_path(), ::std::any::type_name::<Self>()],
+                })
+            },
+        };
+
+        //The args that aren't dispatched are unused if the method only returns an error
+        ln_impls.extend(quote! {
+            #[allow(unused_variables)]
+            #dyn_sig_tokens {
+                #downcast_tokens
+                #end_tokens
+            }
+        });
+
+        if prototype.fallback_body.is_none() {
+            let can_sig_tokens = render_can_sig(orig_fn_name, prototype, &[DYN_TYPE_STRING.to_string()], role_types);
+            ln_impls.extend(quote! {
+                #[allow(unused_variables)]
+                #can_sig_tokens {
+                    #can_downcast_tokens
+                    false
+                }
+            });
+        }
+    }
+
+    Ok(ln_impls)
+}
+
+//Renders the impls of the traits of an extensible family for the types added by an extension.  The impls are in an
+// anonymous const that imports everything from the module of the family, so the names from its declarations resolve
+#[allow(clippy::too_many_arguments)]
+fn render_extension(
+    extension: &Extension,
+    traits: &[DynTrait],
+    trait_types: &[TypeMap],
+    role_types: &[&TypeMap],
+    pairs_map: &HashMap<Vec<String>, FnImpls>,
+    pair_sources: &HashMap<Vec<String>, ImplSource>,
+    fn_sigs: &FnSigs,
+    ln_sigs: &LnSigs) -> Result<TokenStream, SyntaxError> {
+
+    //The family already dispatches the combinations of its own types, so every combination must include a new type
+    let new_types: HashSet<String> = extension.types.iter().map(type_string).collect();
+    for (types, source) in pair_sources.iter() {
+        if !types.iter().any(|type_string| new_types.contains(type_string)) {
+            return Err(SyntaxError {
+                message: format!("<{}> doesn't include a type added by `#![extend(..)]`", type_display_names(types, role_types).join(", ")),
+                span: source.span,
+            });
+        }
+    }
+
+    let mut sorted_types: Vec<Vec<String>> = role_types.iter().map(|type_map| type_map.keys().cloned().collect()).collect();
+    for type_strings in sorted_types.iter_mut() {
+        type_strings.sort();
+    }
+
+    //The methods for the new types call the fallbacks, which are private to the family, so they are emitted again
+    let mut impl_tokens = TokenStream::new();
+    for (orig_fn_name, prototype) in fn_sigs.iter() {
+        impl_tokens.extend(render_fallback_fn(orig_fn_name, prototype)?);
+    }
+    for type_tokens in extension.types.iter() {
+        let type_name = type_string(type_tokens);
+        let mut is_used = false;
+        for (dyn_trait, type_map) in traits.iter().zip(trait_types.iter()) {
+
+            //A type that isn't used by any impl block can still join a family with only one trait
+            if !type_map.contains_key(&type_name) && traits.len() > 1 {
+                continue;
+            }
+            is_used = true;
+
+            let mut method_impls = render_any_fns();
+            if dyn_trait.roles.contains(&0) && fn_sigs.values().any(|prototype| prototype.is_reduce) {
+                method_impls.extend(render_clone_box_fn(&dyn_trait.name));
+            }
+            if dyn_trait.roles.contains(&0) {
+                method_impls.extend(render_dyn_l1_fns(&type_name, &sorted_types[1], pairs_map, role_types, fn_sigs, ln_sigs)?);
+            }
+            if dyn_trait.roles.contains(&1) {
+                method_impls.extend(render_dyn_ln_fns(&sorted_types[0], &type_name, pairs_map, role_types, fn_sigs, ln_sigs)?);
+            }
+
+            let trait_name = &dyn_trait.name;
+            let type_display_name = type_display_string(type_tokens);
+            impl_tokens.extend(quote! {
+                #[allow(clippy::ptr_arg, clippy::unnecessary_cast, clippy::boxed_local)]
+                impl #trait_name for #type_tokens {
+                    fn double_dyn_type_name(&self) -> &'static str {
+                        #type_display_name
+                    }
+
+                    #method_impls
+                }
+            });
+
+            //The ordinals only cover the types of the family, so the new types are out of range and the "_batch"
+            // functions send their calls through the plain function
+            if fn_sigs.values().any(|prototype| prototype.is_batch) {
+                impl_tokens.extend(render_ordinal_impl(trait_name, type_tokens, quote! { usize::MAX }));
+            }
+        }
+
+        if !is_used {
+            return Err(SyntaxError {
+                message: format!("`{}` isn't used by any impl block, so its trait can't be inferred", type_display_string(type_tokens)),
+                span: type_tokens.clone().into_iter().next().map_or_else(Span::call_site, |token| token.span()),
+            });
+        }
+    }
+
+    let path = &extension.path;
+    Ok(quote! {
+        const _: () = {
+            use #path::*;
+
+            #impl_tokens
+        };
+    })
+}
+
+//Renders the body of a method that has dispatched every arg, given the concrete types, which calls either the fn
+// from the impl block or the fallback.  Returns None if the function isn't implemented for the types
+fn render_dispatched_call(
+    orig_fn_name: &str,
+    prototype: &FnPrototype,
+    types: &[String],
+    self_role_idx: usize,
+    pairs_map: &HashMap<Vec<String>, FnImpls>) -> Result<Option<TokenStream>, SyntaxError> {
+
+    let FnPrototype{sig, arg_idxs, arg_kinds, fallback_body, ..} = prototype;
+    match (pairs_map.get(types).and_then(|pair_fn_map| pair_fn_map.get(orig_fn_name)), fallback_body) {
+        (Some((pair_fn_sig, pair_fn_body)), _) => {
+
+            //Declare the fn from the impl block as a nested fn, and then call it
+            let impl_fn_name = implementation_fn_name(pair_fn_sig.fn_name.span());
+            let mut impl_sig = pair_fn_sig.clone();
+            impl_sig.pub_qualifiers = TokenStream::new();
+            impl_sig.fn_name = impl_fn_name.clone();
+            let impl_sig_tokens = render_fn_signature(impl_sig)?;
+            let arg_name_tokens = render_call_args(sig, arg_idxs, arg_kinds, self_role_idx, Some(pair_fn_sig));
+
+            Ok(Some(quote! {
+                #impl_sig_tokens {
+                    #pair_fn_body
+                }
+
+                Ok(#impl_fn_name(#arg_name_tokens))
+            }))
+        },
+        (None, Some(_)) => {
+
+            //Call the fallback, which is emitted once for the function by render_fallback_fn, with the original args
+            let fallback_fn_name = fallback_fn_name(orig_fn_name, sig.fn_name.span());
+            let arg_name_tokens = render_call_args(sig, arg_idxs, arg_kinds, self_role_idx, None);
+
+            Ok(Some(quote! {
+                Ok(#fallback_fn_name(#arg_name_tokens))
+            }))
+        },
+        (None, None) => Ok(None)
+    }
+}
+
+//Returns whether a function is implemented by an impl block for a combination of types
+fn is_implemented(orig_fn_name: &str, types: &[String], pairs_map: &HashMap<Vec<String>, FnImpls>) -> bool {
+    pairs_map.get(types).is_some_and(|fn_impls| fn_impls.contains_key(orig_fn_name))
+}
+
+//The name of the trait method that answers the "can_" predicate of a function for the arg after the prefix, e.g.
+// "l2_can_multiply_i32"
+fn can_method_name(orig_fn_name: &str, prefix: &[String], span: Span) -> Ident {
+    let mut can_fn_name = format!("l{}_can_{}", prefix.len() + 1, orig_fn_name);
+    for type_string in prefix.iter() {
+        can_fn_name.push('_');
+        can_fn_name.push_str(type_string);
+    }
+    Ident::new(&can_fn_name, span)
+}
+
+//Renders the signature of the trait method that answers the "can_" predicate of a function, given the types of the
+// args dispatched before self.  Like the ln methods, it takes the dispatched args that come after self, followed by
+// the args before self as their concrete types, but every arg is a reference, as it is for the "can_" predicate
+fn render_can_sig(orig_fn_name: &str, prototype: &FnPrototype, prefix: &[String], role_types: &[&TypeMap]) -> TokenStream {
+    let FnPrototype{sig, arg_idxs, arg_kinds, ..} = prototype;
+    let can_fn_name = can_method_name(orig_fn_name, prefix, sig.fn_name.span());
+
+    let mut arg_tokens = TokenStream::new();
+    for (role_idx, &arg_idx) in arg_idxs.iter().enumerate().skip(prefix.len() + 1) {
+        let arg_name = sig.args[arg_idx].arg_name.clone().unwrap();
+        let arg_type = can_arg_type(&sig.args[arg_idx].arg_type, arg_kinds[role_idx]);
+        arg_tokens.extend(quote! { #arg_name: #arg_type, });
+    }
+    for (role_idx, type_string) in prefix.iter().enumerate() {
+        let arg_idx = arg_idxs[role_idx];
+        let arg_name = sig.args[arg_idx].arg_name.clone().unwrap();
+
+        //The A arg of an extensible family may have a type the family doesn't know about
+        let arg_type = if type_string == DYN_TYPE_STRING {
+            can_arg_type(&sig.args[arg_idx].arg_type, arg_kinds[role_idx])
+        } else {
+            let type_tokens = role_types[role_idx].get(type_string).unwrap();
+            quote! { &#type_tokens }
+        };
+        arg_tokens.extend(quote! { #arg_name: #arg_type, });
+    }
+
+    quote! {
+        fn #can_fn_name(&self, #arg_tokens) -> bool
+    }
+}
+
+//The type of a dispatched arg for a "can_" predicate, which is a reference to the trait object, e.g. "&dyn MyTraitA"
+// for "Box<dyn MyTraitA>", or an optional reference if the arg is optional
+fn can_arg_type(arg_type: &TokenStream, arg_kind: ArgKind) -> TokenStream {
+    let tokens: Vec<TokenTree> = arg_type.clone().into_iter().collect();
+    let dyn_idx = tokens.iter().position(|token| matches!(token, TokenTree::Ident(ident) if ident == "dyn")).unwrap();
+    let trait_tokens: TokenStream = tokens[dyn_idx..dyn_idx + 2].iter().cloned().collect();
+    if arg_kind == ArgKind::OptionRef {
+        quote! { Option<&#trait_tokens> }
+    } else {
+        quote! { &#trait_tokens }
+    }
+}
+
+//The name of the nested fn that an implementation from an impl block is declared as.  It is declared in the body of
+// a generated fn, so its name mustn't shadow anything the implementation might refer to
+fn implementation_fn_name(span: Span) -> Ident {
+    Ident::new("__double_dyn_implementation", span)
+}
+
+//The name of the free fn that holds the fallback body of a function, e.g. "__double_dyn_fallback_multiply"
+fn fallback_fn_name(orig_fn_name: &str, span: Span) -> Ident {
+    Ident::new(&format!("__double_dyn_fallback_{}", orig_fn_name), span)
+}
+
+//Renders the fallback body of a function as a private free fn with the prototype's signature, so that the methods for
+// every combination of types without an implementation can share it.  Renders nothing if there is no fallback body
+fn render_fallback_fn(orig_fn_name: &str, prototype: &FnPrototype) -> Result<TokenStream, SyntaxError> {
+    let FnPrototype{sig, fallback_body, ..} = prototype;
+    let fallback_body = match fallback_body {
+        Some(fallback_body) => fallback_body,
+        None => return Ok(TokenStream::new()),
+    };
+
+    let mut fallback_sig = sig.clone();
+    fallback_sig.pub_qualifiers = TokenStream::new();
+    fallback_sig.fn_name = fallback_fn_name(orig_fn_name, sig.fn_name.span());
+    let fallback_sig_tokens = render_fn_signature(fallback_sig)?;
+
+    //The fallback isn't called if every combination of types is implemented
+    Ok(quote! {
+        #[allow(dead_code, clippy::ptr_arg, clippy::boxed_local)]
+        #fallback_sig_tokens {
+            #fallback_body
+        }
+    })
+}
+
+//Renders an error that names the concrete types, for a function that isn't implemented for the types
+fn render_dispatch_error(orig_fn_name: &str, types: &[String], self_role_idx: usize, role_types: &[&TypeMap]) -> TokenStream {
+    let mut type_name_tokens = TokenStream::new();
+    for (role_idx, type_string) in types.iter().enumerate() {
+        if role_idx == self_role_idx {
+            type_name_tokens.extend(quote! { ::std::any::type_name::<Self>(), });
+        } else if type_string == NONE_TYPE_STRING {
+            type_name_tokens.extend(quote! { "None", });
+        } else {
+            let type_tokens = role_types[role_idx].get(type_string).unwrap();
+            type_name_tokens.extend(quote! { ::std::any::type_name::<#type_tokens>(), });
+        }
+    }
+
+    quote! {
+        Err(::double_dyn::DispatchError {
+            fn_name: #orig_fn_name,
+            type_names: vec![#type_name_tokens],
+        })
+    }
+}
+
+//Renders the body of the "try_" variant of a function for the table backend.  The ordinals of the args index a table
+// of function pointers, with one entry for each combination of types, which is either a function that casts the
+// args to their concrete types and calls the implementation, or None
+fn render_table_dispatch(
+    orig_fn_name: &str,
+    prototype: &FnPrototype,
+    pairs_map: &HashMap<Vec<String>, FnImpls>,
+    role_traits: &[usize],
+    role_types: &[&TypeMap],
+    trait_ordinals: &[HashMap<String, usize>]) -> Result<TokenStream, SyntaxError> {
+
+    let FnPrototype{sig, arg_idxs, arg_kinds, fallback_body, ..} = prototype;
+    let arg_names: Vec<Ident> = sig.args.iter().map(|arg| arg.arg_name.clone().unwrap()).collect();
+    let arg_types: Vec<&TokenStream> = sig.args.iter().map(|arg| &arg.arg_type).collect();
+    let result = if sig.result.is_empty() {
+        quote! { () }
+    } else {
+        sig.result.clone()
+    };
+
+    let (dims, ordinal_exprs, index_tokens) = render_table_index(prototype, role_types);
+
+    //Render an entry for every implemented combination of types
+    let mut entries: Vec<Option<Ident>> = vec![None; dims.iter().product()];
+    let mut entry_fn_tokens = TokenStream::new();
+    for (types, fn_impls) in pairs_map.iter() {
+        let (pair_fn_sig, pair_fn_body) = match fn_impls.get(orig_fn_name) {
+            Some(pair_fn) => pair_fn,
+            None => continue,
+        };
+
+        let mut impl_args = vec![];
+        for (i, arg_name) in arg_names.iter().enumerate() {
+            impl_args.push(match arg_idxs.iter().position(|&arg_idx| arg_idx == i) {
+                Some(role_idx) if types[role_idx] == NONE_TYPE_STRING => quote! { #arg_name },
+                Some(role_idx) => {
+                    let type_tokens = role_types[role_idx].get(&types[role_idx]).unwrap();
+                    let cast_tokens = arg_kinds[role_idx].unchecked_cast(&quote! { #arg_name }, type_tokens);
+
+                    //SAFETY: The ordinal trait is unsafe to implement, and its impls promise that the ordinal identifies
+                    // the concrete type behind the trait object, so the cast is valid
+                    if arg_kinds[role_idx] == ArgKind::Boxed && !is_boxed_type(&pair_fn_sig.args[i].arg_type) {
+                        quote! { *unsafe { #cast_tokens } }
+                    } else {
+                        quote! { unsafe { #cast_tokens } }
+                    }
+                },
+                None => quote! { #arg_name },
+            });
+        }
+        let index = table_entry_index(types, &dims, role_traits, role_types, trait_ordinals);
+
+        let impl_fn_name = implementation_fn_name(pair_fn_sig.fn_name.span());
+        let mut impl_sig = pair_fn_sig.clone();
+        impl_sig.pub_qualifiers = TokenStream::new();
+        impl_sig.fn_name = impl_fn_name.clone();
+        let impl_sig_tokens = render_fn_signature(impl_sig)?;
+
+        //The entry takes the args as they are passed to the prototype
+        let entry_fn_name = Ident::new(&format!("__double_dyn_entry_{}", index), sig.fn_name.span());
+        let mut entry_sig = sig.clone();
+        entry_sig.pub_qualifiers = TokenStream::new();
+        entry_sig.fn_name = entry_fn_name.clone();
+        for arg in entry_sig.args.iter_mut() {
+            arg.is_mut = false;
+        }
+        let entry_sig_tokens = render_fn_signature(entry_sig)?;
+
+        entry_fn_tokens.extend(quote! {
+            #entry_sig_tokens {
+                #impl_sig_tokens {
+                    #pair_fn_body
+                }
+
+                #impl_fn_name(#(#impl_args),*)
+            }
+        });
+        entries[index] = Some(entry_fn_name);
+    }
+    let entry_tokens: Vec<TokenStream> = entries.into_iter().map(|entry| match entry {
+        Some(entry_fn_name) => quote! { Some(#entry_fn_name) },
+        None => quote! { None },
+    }).collect();
+    let entry_count = entry_tokens.len();
+
+    //A combination of types without an entry calls the fallback if there is one, or returns an error
+    let missing_tokens = match fallback_body {
+        Some(fallback_body) => {
+            let fallback_fn_name = fallback_fn_name(orig_fn_name, sig.fn_name.span());
+            let mut fallback_sig = sig.clone();
+            fallback_sig.pub_qualifiers = TokenStream::new();
+            fallback_sig.fn_name = fallback_fn_name.clone();
+            let fallback_sig_tokens = render_fn_signature(fallback_sig)?;
+            quote! {
+                #fallback_sig_tokens {
+                    #fallback_body
+                }
+
+                Ok(#fallback_fn_name(#(#arg_names),*))
+            }
+        },
+        None => {
+            let mut type_name_tokens = TokenStream::new();
+            for (role_idx, ordinal_expr) in ordinal_exprs.iter().enumerate() {
+                let mut type_strings: Vec<&String> = role_types[role_idx].keys().collect();
+                type_strings.sort();
+                let type_tokens = type_strings.into_iter().map(|type_string| role_types[role_idx].get(type_string).unwrap());
+                let none_tokens = if arg_kinds[role_idx] == ArgKind::OptionRef {
+                    quote! { "None" }
+                } else {
+                    TokenStream::new()
+                };
+                type_name_tokens.extend(quote! {
+                    [#(::std::any::type_name::<#type_tokens>(),)* #none_tokens].get(#ordinal_expr).copied().unwrap_or("{unknown}"),
+                });
+            }
+            quote! {
+                Err(::double_dyn::DispatchError {
+                    fn_name: #orig_fn_name,
+                    type_names: vec![#type_name_tokens],
+                })
+            }
+        }
+    };
+
+    //The items are declared in the body of the generated fn, so their names mustn't shadow the types of the args
+    Ok(quote! {
+        type __DoubleDynEntry = fn(#(#arg_types),*) -> #result;
+
+        #entry_fn_tokens
+
+        static __DOUBLE_DYN_TABLE: [Option<__DoubleDynEntry>; #entry_count] = [#(#entry_tokens),*];
+
+        match #index_tokens.and_then(|index| __DOUBLE_DYN_TABLE[index]) {
+            Some(__double_dyn_entry) => Ok(__double_dyn_entry(#(#arg_names),*)),
+            None => {
+                #missing_tokens
+            }
+        }
+    })
+}
+
+//Renders the registry for a function with the registry backend, which is a static named after the function, e.g.
+// "MULTIPLY", of a type named after the function, e.g. "MultiplyRegistry".  Its "register" method takes the
+// implementation for a pair of concrete types, and wraps it in an entry that takes the dispatched args as "&dyn Any".
+// The registry starts out with an entry for every combination of types implemented by the impl blocks
+fn render_registry(
+    orig_fn_name: &str,
+    prototype: &FnPrototype,
+    pairs_map: &HashMap<Vec<String>, FnImpls>,
+    role_traits: &[usize],
+    traits: &[DynTrait],
+    role_types: &[&TypeMap],
+    pub_qualifiers: &TokenStream) -> Result<TokenStream, SyntaxError> {
+
+    let FnPrototype{sig, arg_idxs, ..} = prototype;
+    let static_name = Ident::new(&format!("{}", AsShoutySnakeCase(orig_fn_name)), sig.fn_name.span());
+    let struct_name = Ident::new(&format!("{}Registry", AsUpperCamelCase(orig_fn_name)), sig.fn_name.span());
+    let result = if sig.result.is_empty() {
+        quote! { () }
+    } else {
+        sig.result.clone()
+    };
+
+    //The type params of "register" are named after the roles of the dispatched args, e.g. "__DoubleDynA", so they
+    // don't shadow types named "A" or "B"
+    let type_params: Vec<Ident> = (0..arg_idxs.len()).map(|role_idx| Ident::new(&format!("__DoubleDyn{}", role_name(role_idx)), sig.fn_name.span())).collect();
+    let trait_names: Vec<&Ident> = role_traits.iter().map(|&trait_idx| &traits[trait_idx].name).collect();
+    let mut entry_arg_types = vec![];
+    let mut typed_arg_types = vec![];
+    let mut closure_args = vec![];
+    let mut downcast_args = vec![];
+    for (i, arg) in sig.args.iter().enumerate() {
+        let arg_name = arg.arg_name.clone().unwrap();
+        let arg_type = &arg.arg_type;
+        match arg_idxs.iter().position(|&arg_idx| arg_idx == i) {
+            Some(role_idx) => {
+                let type_param = &type_params[role_idx];
+                entry_arg_types.push(quote! { &dyn ::std::any::Any });
+                typed_arg_types.push(quote! { &#type_param });
+                closure_args.push(quote! { #arg_name: &dyn ::std::any::Any });
+                downcast_args.push(quote! { #arg_name.downcast_ref::<#type_param>().unwrap() });
+            },
+            None => {
+                entry_arg_types.push(arg_type.clone());
+                typed_arg_types.push(arg_type.clone());
+                closure_args.push(quote! { #arg_name: #arg_type });
+                downcast_args.push(quote! { #arg_name });
+            },
+        }
+    }
+    let entry_type = quote! { dyn Fn(#(#entry_arg_types),*) -> #result + Send + Sync };
+    let typed_fn_type = quote! { impl Fn(#(#typed_arg_types),*) -> #result + Send + Sync + 'static };
+
+    //Every implemented combination of types gets an entry that calls the implementation as a nested fn
+    let mut init_tokens = TokenStream::new();
+    for (types, fn_impls) in pairs_map.iter() {
+        let (pair_fn_sig, pair_fn_body) = match fn_impls.get(orig_fn_name) {
+            Some(pair_fn) => pair_fn,
+            None => continue,
+        };
+
+        let impl_fn_name = implementation_fn_name(pair_fn_sig.fn_name.span());
+        let mut impl_sig = pair_fn_sig.clone();
+        impl_sig.pub_qualifiers = TokenStream::new();
+        impl_sig.fn_name = impl_fn_name.clone();
+        let impl_sig_tokens = render_fn_signature(impl_sig)?;
+        let type_tokens: Vec<&TokenStream> = types.iter().enumerate().map(|(role_idx, type_string)| role_types[role_idx].get(type_string).unwrap()).collect();
+
+        init_tokens.extend(quote! {
+            ((#(::std::any::TypeId::of::<#type_tokens>()),*), #struct_name::entry::<#(#type_tokens),*>({
+                #impl_sig_tokens {
+                    #pair_fn_body
+                }
+
+                #impl_fn_name
+            })),
+        });
+    }
+
+    Ok(quote! {
+        #[allow(clippy::type_complexity)]
+        #pub_qualifiers struct #struct_name(::double_dyn::Registry<#entry_type>);
+
+        #[allow(clippy::type_complexity)]
+        impl #struct_name {
+            #pub_qualifiers fn register<#(#type_params: #trait_names + 'static),*>(&self, f: #typed_fn_type) {
+                self.0.insert((#(::std::any::TypeId::of::<#type_params>()),*), Self::entry(f));
+            }
+
+            fn entry<#(#type_params: 'static),*>(f: #typed_fn_type) -> ::std::sync::Arc<#entry_type> {
+                ::std::sync::Arc::new(move |#(#closure_args),*| f(#(#downcast_args),*))
+            }
+        }
+
+        impl ::std::ops::Deref for #struct_name {
+            type Target = ::double_dyn::Registry<#entry_type>;
+
+            fn deref(&self) -> &Self::Target {
+                &self.0
+            }
+        }
+
+        #[allow(clippy::ptr_arg, clippy::unnecessary_cast, clippy::boxed_local)]
+        #pub_qualifiers static #static_name: #struct_name = #struct_name(::double_dyn::Registry::new(#orig_fn_name, || vec![#init_tokens]));
+    })
+}
+
+//Renders the body of the "try_" variant of a function for the registry backend, which looks up the entry for the
+// TypeIds of the concrete types behind the args
+fn render_registry_dispatch(orig_fn_name: &str, prototype: &FnPrototype) -> Result<TokenStream, SyntaxError> {
+    let FnPrototype{sig, arg_idxs, fallback_body, ..} = prototype;
+    let static_name = Ident::new(&format!("{}", AsShoutySnakeCase(orig_fn_name)), sig.fn_name.span());
+    let arg_names: Vec<Ident> = sig.args.iter().map(|arg| arg.arg_name.clone().unwrap()).collect();
+    let dispatched_names: Vec<&Ident> = arg_idxs.iter().map(|&arg_idx| &arg_names[arg_idx]).collect();
+    let entry_args: Vec<TokenStream> = arg_names.iter().enumerate().map(|(i, arg_name)| if arg_idxs.contains(&i) {
+        quote! { #arg_name.double_dyn_as_any() }
+    } else {
+        quote! { #arg_name }
+    }).collect();
+
+    //A combination of types without an entry calls the fallback if there is one, or returns an error
+    let missing_tokens = match fallback_body {
+        Some(fallback_body) => {
+            let fallback_fn_name = fallback_fn_name(orig_fn_name, sig.fn_name.span());
+            let mut fallback_sig = sig.clone();
+            fallback_sig.pub_qualifiers = TokenStream::new();
+            fallback_sig.fn_name = fallback_fn_name.clone();
+            let fallback_sig_tokens = render_fn_signature(fallback_sig)?;
+            quote! {
+                #fallback_sig_tokens {
+                    #fallback_body
+                }
+
+                Ok(#fallback_fn_name(#(#arg_names),*))
+            }
+        },
+        None => quote! {
+            Err(::double_dyn::DispatchError {
+                fn_name: #orig_fn_name,
+                type_names: vec![#(#dispatched_names.double_dyn_type_name()),*],
+            })
+        },
+    };
+
+    //The entry is cloned out of the registry, so the lock isn't held while it runs
+    Ok(quote! {
+        match #static_name.get((#(#dispatched_names.double_dyn_as_any().type_id()),*)) {
+            Some(__double_dyn_entry) => Ok(__double_dyn_entry(#(#entry_args),*)),
+            None => {
+                #missing_tokens
+            }
+        }
+    })
+}
+
+//Renders the index into the table of a function for the table backend, along with the dimension of the table and the
+// expression for the ordinal of each arg.  The dimension for each arg is the number of types, plus one for None if the
+// arg is optional.  The table is flattened, so the index is computed from the ordinals in row-major order.  An ordinal
+// that is out of range for its arg would alias the entry of another combination of types, so the index is None if any
+// of the ordinals are out of range
+fn render_table_index(prototype: &FnPrototype, role_types: &[&TypeMap]) -> (Vec<usize>, Vec<TokenStream>, TokenStream) {
+    let FnPrototype{sig, arg_idxs, arg_kinds, ..} = prototype;
+    let mut dims = vec![];
+    let mut ordinal_exprs = vec![];
+    for (role_idx, &arg_idx) in arg_idxs.iter().enumerate() {
+        let type_count = role_types[role_idx].len();
+        let arg_name = sig.args[arg_idx].arg_name.clone().unwrap();
+        if arg_kinds[role_idx] == ArgKind::OptionRef {
+            dims.push(type_count + 1);
+            ordinal_exprs.push(quote! { #arg_name.map_or(#type_count, |#arg_name| #arg_name.double_dyn_type_ordinal()) });
+        } else {
+            dims.push(type_count);
+            ordinal_exprs.push(quote! { #arg_name.double_dyn_type_ordinal() });
+        }
+    }
+    let index_tokens = quote! {
+        {
+            let ordinals = [#(#ordinal_exprs),*];
+            let dims = [#(#dims),*];
+            if ordinals.iter().zip(dims.iter()).all(|(ordinal, dim)| ordinal < dim) {
+                Some(ordinals.iter().zip(dims.iter()).fold(0usize, |index, (ordinal, dim)| index * dim + ordinal))
+            } else {
+                None
+            }
+        }
+    };
+    (dims, ordinal_exprs, index_tokens)
+}
+
+//Computes the index of the entry for a combination of types in the table of a function for the table backend, where
+// None comes after the types of an optional arg
+fn table_entry_index(types: &[String], dims: &[usize], role_traits: &[usize], role_types: &[&TypeMap], trait_ordinals: &[HashMap<String, usize>]) -> usize {
+    let mut index = 0;
+    for (role_idx, type_string) in types.iter().enumerate() {
+        let ordinal = trait_ordinals[role_traits[role_idx]].get(type_string).copied().unwrap_or(role_types[role_idx].len());
+        index = index * dims[role_idx] + ordinal;
+    }
+    index
+}
+
+//Renders the enum that takes the place of a trait with the enum backend, with a variant for each type in the order
+// of the ordinals, along with the "From" impls and the methods the trait would have had
+fn render_enum(enum_name: &Ident, type_map: &TypeMap, ordinals: &HashMap<String, usize>, pub_qualifiers: &TokenStream) -> TokenStream {
+    let mut type_strings: Vec<&String> = type_map.keys().collect();
+    type_strings.sort_by_key(|&type_string| ordinals[type_string]);
+
+    let mut variant_tokens = TokenStream::new();
+    let mut from_tokens = TokenStream::new();
+    let mut type_name_arm_tokens = TokenStream::new();
+    let mut ordinal_arm_tokens = TokenStream::new();
+    for type_string in type_strings {
+        let type_tokens = &type_map[type_string];
+        let variant_name = enum_variant_name(type_tokens, enum_name.span());
+        let type_display_name = type_display_string(type_tokens);
+        let ordinal = ordinals[type_string];
+
+        variant_tokens.extend(quote! { #variant_name(#type_tokens), });
+        from_tokens.extend(quote! {
+            impl From<#type_tokens> for #enum_name {
+                fn from(value: #type_tokens) -> Self {
+                    #enum_name::#variant_name(value)
+                }
+            }
+        });
+        type_name_arm_tokens.extend(quote! { #enum_name::#variant_name(_) => #type_display_name, });
+        ordinal_arm_tokens.extend(quote! { #enum_name::#variant_name(_) => #ordinal, });
+    }
+
+    quote! {
+        #pub_qualifiers enum #enum_name {
+            #variant_tokens
+        }
+
+        #from_tokens
+
+        impl #enum_name {
+            #pub_qualifiers fn double_dyn_type_name(&self) -> &'static str {
+                match self {
+                    #type_name_arm_tokens
+                }
+            }
+
+            #pub_qualifiers fn double_dyn_type_ordinal(&self) -> usize {
+                match self {
+                    #ordinal_arm_tokens
+                }
+            }
+        }
+    }
+}
+
+//Returns the name of the enum variant for a type with the enum backend, e.g. "VecU8" for "Vec<u8>"
+fn enum_variant_name(type_tokens: &TokenStream, span: Span) -> Ident {
+    Ident::new(&format!("{}", AsUpperCamelCase(type_display_string(type_tokens))), span)
+}
+
+//Replaces "dyn MyTrait" in the type of a dispatched arg with the enum of the same name, e.g. "&dyn MyTrait" becomes
+// "&MyTrait"
+fn replace_dyn_trait(arg_type: &TokenStream, trait_name: &Ident) -> TokenStream {
+    let tokens: Vec<TokenTree> = arg_type.clone().into_iter().collect();
+    let mut new_tokens = TokenStream::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let is_dyn = matches!(token, TokenTree::Ident(ident) if *ident == "dyn");
+        let is_before_trait = matches!(tokens.get(i + 1), Some(TokenTree::Ident(ident)) if ident == trait_name);
+        if !(is_dyn && is_before_trait) {
+            new_tokens.extend([token.clone()]);
+        }
+    }
+    new_tokens
+}
+
+//Renders the body of the "try_" variant of a function for the enum backend, which matches on the variants of every
+// dispatched arg at once
+fn render_enum_dispatch(
+    orig_fn_name: &str,
+    prototype: &FnPrototype,
+    pairs_map: &HashMap<Vec<String>, FnImpls>,
+    role_traits: &[usize],
+    traits: &[DynTrait],
+    role_types: &[&TypeMap]) -> Result<TokenStream, SyntaxError> {
+
+    let FnPrototype{sig, arg_idxs, arg_kinds, fallback_body, ..} = prototype;
+    let arg_names: Vec<Ident> = sig.args.iter().map(|arg| arg.arg_name.clone().unwrap()).collect();
+    let dispatched_arg_names: Vec<&Ident> = arg_idxs.iter().map(|&arg_idx| &arg_names[arg_idx]).collect();
+
+    //A mutable reference is reborrowed, so the arg is still available to the last arm
+    let scrutinee_tokens: Vec<TokenStream> = dispatched_arg_names.iter().zip(arg_kinds.iter()).map(|(arg_name, arg_kind)| match arg_kind {
+        ArgKind::RefMut => quote! { &mut *#arg_name },
+        _ => quote! { #arg_name },
+    }).collect();
+
+    //Each implemented combination of types gets a match arm that calls the implementation
+    let mut match_arm_tokens = TokenStream::new();
+    for (types, fn_impls) in pairs_map.iter() {
+        let (pair_fn_sig, pair_fn_body) = match fn_impls.get(orig_fn_name) {
+            Some(pair_fn) => pair_fn,
+            None => continue,
+        };
+
+        let mut pattern_tokens = TokenStream::new();
+        for (role_idx, type_string) in types.iter().enumerate() {
+            let enum_name = &traits[role_traits[role_idx]].name;
+            let variant_name = enum_variant_name(&role_types[role_idx][type_string], enum_name.span());
+            let arg_name = dispatched_arg_names[role_idx];
+            pattern_tokens.extend(quote! { #enum_name::#variant_name(#arg_name), });
+        }
+
+        let impl_fn_name = implementation_fn_name(pair_fn_sig.fn_name.span());
+        let mut impl_sig = pair_fn_sig.clone();
+        impl_sig.pub_qualifiers = TokenStream::new();
+        impl_sig.fn_name = impl_fn_name.clone();
+        let impl_sig_tokens = render_fn_signature(impl_sig)?;
+
+        match_arm_tokens.extend(quote! {
+            (#pattern_tokens) => {
+                #impl_sig_tokens {
+                    #pair_fn_body
+                }
+
+                Ok(#impl_fn_name(#(#arg_names),*))
+            },
+        });
+    }
+
+    //A combination of types without an implementation calls the fallback if there is one, or returns an error
+    let missing_tokens = match fallback_body {
+        Some(fallback_body) => {
+            let fallback_fn_name = fallback_fn_name(orig_fn_name, sig.fn_name.span());
+            let mut fallback_sig = sig.clone();
+            fallback_sig.pub_qualifiers = TokenStream::new();
+            fallback_sig.fn_name = fallback_fn_name.clone();
+            for (&arg_idx, &trait_idx) in arg_idxs.iter().zip(role_traits.iter()) {
+                fallback_sig.args[arg_idx].arg_type = replace_dyn_trait(&sig.args[arg_idx].arg_type, &traits[trait_idx].name);
+            }
+            let fallback_sig_tokens = render_fn_signature(fallback_sig)?;
+            quote! {
+                #fallback_sig_tokens {
+                    #fallback_body
+                }
+
+                Ok(#fallback_fn_name(#(#arg_names),*))
+            }
+        },
+        None => {
+            let mut type_name_tokens = TokenStream::new();
+            for (role_idx, arg_name) in dispatched_arg_names.iter().enumerate() {
+                let enum_name = &traits[role_traits[role_idx]].name;
+                let mut type_arm_tokens = TokenStream::new();
+                for type_tokens in role_types[role_idx].values() {
+                    let variant_name = enum_variant_name(type_tokens, enum_name.span());
+                    type_arm_tokens.extend(quote! { #enum_name::#variant_name(_) => ::std::any::type_name::<#type_tokens>(), });
+                }
+                type_name_tokens.extend(quote! {
+                    match #arg_name {
+                        #type_arm_tokens
+                    },
+                });
+            }
+            quote! {
+                Err(::double_dyn::DispatchError {
+                    fn_name: #orig_fn_name,
+                    type_names: vec![#type_name_tokens],
+                })
+            }
+        }
+    };
+
+    //The patterns bind the values inside the variants to the names of the args, so every arm can pass the args on by
+    // name.  If every combination is implemented, then the last arm is unreachable
+    Ok(quote! {
+        match (#(#scrutinee_tokens,)*) {
+            #match_arm_tokens
+            _ => {
+                #missing_tokens
+            }
+        }
+    })
+}
+
+//Renders the args for a call from a method that has dispatched every arg to a fn with the prototype's arg order,
+// where self takes the place of the arg that was dispatched last, and any arg after it is an optional arg that is
+// None.  If the fn is an implementation from an impl block that takes a boxed arg by value, then the arg is unboxed,
+// and if the fn is the fallback then an optional arg is wrapped back up in an Option
+fn render_call_args(sig: &FnSignature, arg_idxs: &[usize], arg_kinds: &[ArgKind], self_role_idx: usize, impl_sig: Option<&FnSignature>) -> TokenStream {
+    let mut arg_name_tokens = TokenStream::new();
+    for (i, arg) in sig.args.iter().enumerate() {
+        let role_idx = arg_idxs.iter().position(|&arg_idx| arg_idx == i);
+        let arg_tokens = match role_idx {
+            Some(role_idx) if role_idx == self_role_idx => quote! { self },
+            Some(role_idx) if role_idx > self_role_idx && arg_kinds[role_idx] == ArgKind::OptionRef => quote! { None },
+            _ => {
+                let arg_name = arg.arg_name.clone().unwrap();
+                quote! { #arg_name }
+            }
+        };
+
+        let arg_tokens = match (role_idx, impl_sig) {
+            (Some(role_idx), Some(impl_sig)) if arg_kinds[role_idx] == ArgKind::Boxed && !is_boxed_type(&impl_sig.args[i].arg_type) => {
+                quote! { *#arg_tokens }
+            },
+            (Some(role_idx), None) if role_idx == self_role_idx && arg_kinds[role_idx] == ArgKind::OptionRef => {
+                quote! { Some(#arg_tokens) }
+            },
+            _ => arg_tokens
+        };
+        arg_name_tokens.extend(quote! { #arg_tokens, });
+    }
+    arg_name_tokens
+}
+
+//Returns whether a type is a Box, e.g. "Box<#A>"
+fn is_boxed_type(type_tokens: &TokenStream) -> bool {
+    matches!(type_tokens.clone().into_iter().next(), Some(TokenTree::Ident(ident)) if ident == "Box")
+}
+
+//Transmutes a fn signature into the method on the trait for the next dispatched arg, given the concrete types of
+// the args that have already been dispatched.  With no types, it turns
+// "fn min_max(val: i32, min: &dyn MyTraitA, max: &dyn MyTraitB) -> Result<i32, String>;" into
+// "fn l1_min_max(&self, val: i32, max: &dyn MyTraitB) -> Result<i32, String>;"
+// and with "i32" for A it becomes
+// "fn l2_min_max_i32(&self, val: i32, min: &i32) -> Result<i32, String>;"
+fn transmute_to_ln_signature(original_sig: FnSignature, prefix: &[String], role_types: &[&TypeMap], arg_idxs: &[usize], arg_kinds: &[ArgKind]) -> Result<(FnSignature, FnArg), SyntaxError> {
+
+    let role_idx = prefix.len();
+    let mut new_fn_name = format!("l{}_{}", role_idx + 1, original_sig.fn_name);
+    for type_string in prefix.iter() {
+        new_fn_name.push('_');
+        new_fn_name.push_str(type_string);
+    }
+    let new_fn_name = Ident::new(&new_fn_name, original_sig.fn_name.span());
+    let mut new_sig = original_sig;
+    new_sig.pub_qualifiers = TokenStream::new(); //no visibility qualifiers on trait methods
+    new_sig.fn_name = new_fn_name;
+    for arg in new_sig.args.iter_mut() {
+        arg.is_mut = false; //"mut" bindings aren't allowed in trait method declarations
+    }
+    new_sig.result = dispatch_result_type(&new_sig.result);
+
+    //Remove the args that have already been dispatched, along with the arg that will be replaced by self.  But we
+    // need to remove them from back to front because we don't want to screw up the indices
+    let mut removed_idxs = arg_idxs[..=role_idx].to_vec();
+    removed_idxs.sort_unstable();
+    let mut removed_args = HashMap::with_capacity(removed_idxs.len());
+    for &arg_idx in removed_idxs.iter().rev() {
+        removed_args.insert(arg_idx, new_sig.args.remove(arg_idx));
+    }
+    let old_self_arg = removed_args.remove(&arg_idxs[role_idx]).unwrap();
+    new_sig.args.insert(0, FnArg{
+        arg_name: None,
+        is_mut: false,
+        arg_type: arg_kinds[role_idx].receiver()
+    });
+
+    //Add the args that have already been dispatched back at the end, as their concrete types
+    for (prefix_idx, type_string) in prefix.iter().enumerate() {
+        let old_arg = removed_args.remove(&arg_idxs[prefix_idx]).unwrap();
+        let type_tokens = role_types[prefix_idx].get(type_string).unwrap();
+        new_sig.args.push(FnArg{
+            arg_name: old_arg.arg_name,
+            is_mut: false,
+            arg_type: arg_kinds[prefix_idx].concrete_type(type_tokens)
+        });
+    }
+
+    Ok((new_sig, old_self_arg))
+}
+
+//Works out how a dispatched arg is passed, from its type in the prototype
+fn dispatched_arg_kind(arg: &FnArg, trait_name: &Ident) -> Result<ArgKind, SyntaxError> {
+    let type_strings: Vec<String> = arg.arg_type.clone().into_iter().map(|token| token.to_string()).collect();
+    let type_strs: Vec<&str> = type_strings.iter().map(|type_string| type_string.as_str()).collect();
+    let trait_string = trait_name.to_string();
+    match type_strs[..] {
+        ["&", "dyn", name] if name == trait_string => Ok(ArgKind::Ref),
+        ["&", "mut", "dyn", name] if name == trait_string => Ok(ArgKind::RefMut),
+        ["Box", "<", "dyn", name, ">"] if name == trait_string => Ok(ArgKind::Boxed),
+        ["Rc", "<", "dyn", name, ">"] if name == trait_string => Ok(ArgKind::Rc),
+        ["Arc", "<", "dyn", name, ">"] if name == trait_string => Ok(ArgKind::Arc),
+        ["Option", "<", "&", "dyn", name, ">"] if name == trait_string => Ok(ArgKind::OptionRef),
+        _ => Err(SyntaxError {
+            message: format!("dispatched arg must be `&dyn {0}`, `&mut dyn {0}`, `Box<dyn {0}>`, `Rc<dyn {0}>`, `Arc<dyn {0}>` or `Option<&dyn {0}>`", trait_name),
+            span: arg.arg_name.as_ref().map(|arg_name| arg_name.span()).unwrap_or_else(Span::call_site),
+        })
+    }
+}
+
+//Replaces "#A", "#B", etc. placeholders with the tokens representing concrete types
+fn replace_type_placeholders(input_stream: TokenStream, types: &[TokenStream]) -> Result<TokenStream, SyntaxError> {
+
+    let mut fn_body_iter = input_stream.into_iter();
+    let mut previous_hash = false;
+    recursive_scan(&mut fn_body_iter, &mut |token, stream| {
+
+        if previous_hash {
+            if let TokenTree::Ident(ident) = token {
+                let ident_string = ident.to_string();
+                match types.iter().enumerate().find(|(role_idx, _)| role_name(*role_idx) == ident_string) {
+                    Some((_, type_tokens)) => {
+                        stream.extend([type_tokens.clone()]);
+                    },
+                    None => return Err(format!("unknown type macro identifier, #{}", ident_string)),
+                };
+                previous_hash = false;
+                return Ok(());
+            } else {
+                return Err("expected special type macro identifier".to_string());
+            }
+        }
+
+        if let TokenTree::Punct(punct) = &token {
+            if punct.as_char() == '#' {
+                previous_hash = true;
+                return Ok(());
+            }
+        }
+
+        stream.extend([token]);
+        Ok(())
+    })
+}
+
+//Replaces "Self" with the tokens of a concrete type.  A path that continues after "Self", e.g. "Self::new()", gets
+// the type as a qualified path, e.g. "<Vec<u8>>::new()", which is valid in an expression.  An impl or trait inside
+// the tokens has its own "Self", so it is left as it is
+fn replace_self_type(input_stream: TokenStream, self_type: &TokenStream) -> TokenStream {
+    let mut new_stream = TokenStream::new();
+    let mut is_item_header = false;
+    let mut iter = input_stream.into_iter().peekable();
+    while let Some(token) = iter.next() {
+        match token {
+            TokenTree::Ident(ident) if ident == "impl" || ident == "trait" => {
+                is_item_header = true;
+                new_stream.extend([TokenTree::Ident(ident)]);
+            },
+            TokenTree::Group(group) if is_item_header && group.delimiter() == Delimiter::Brace => {
+                is_item_header = false;
+                new_stream.extend([TokenTree::Group(group)]);
+            },
+            token if is_item_header => new_stream.extend([token]),
+            TokenTree::Ident(ident) if ident == "Self" => {
+                let is_path = matches!(iter.peek(), Some(TokenTree::Punct(punct)) if punct.as_char() == ':');
+                if is_path {
+                    new_stream.extend(quote_spanned! { ident.span() => <#self_type> });
+                } else {
+                    new_stream.extend(self_type.clone());
+                }
+            },
+            TokenTree::Group(group) => {
+                let mut new_group = Group::new(group.delimiter(), replace_self_type(group.stream(), self_type));
+                new_group.set_span(group.span());
+                new_stream.extend([TokenTree::Group(new_group)]);
+            },
+            token => new_stream.extend([token]),
+        }
+    }
+    new_stream
+}
+
+//Renders a type the way it would normally be written, e.g. "HashMap<String, Vec<u8>>", for use in strings that
+// are meant to be read
+fn type_display_string(type_tokens: &TokenStream) -> String {
+    let mut out_string = String::new();
+    let mut previous_word = false;
+    for token in type_tokens.clone().into_iter() {
+        match token {
+            TokenTree::Ident(_) | TokenTree::Literal(_) => {
+                if previous_word {
+                    out_string.push(' ');
+                }
+                out_string.push_str(&token.to_string());
+                previous_word = true;
+            },
+            TokenTree::Punct(punct) => {
+                out_string.push(punct.as_char());
+                if punct.as_char() == ',' || punct.as_char() == ';' {
+                    out_string.push(' ');
+                }
+                previous_word = false;
+            },
+            TokenTree::Group(group) => {
+                let (open_delim, close_delim) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::None => ("", ""),
+                };
+                out_string.push_str(open_delim);
+                out_string.push_str(&type_display_string(&group.stream()));
+                out_string.push_str(close_delim);
+                previous_word = false;
+            }
+        }
+    }
+    out_string
+}
+
+fn tokens_to_string(tokens: TokenStream) -> String {
+    let mut out_string = "".to_string();
+    for token in tokens.into_iter() {
+        match token {
+            TokenTree::Ident(ident) => {
+                out_string.push_str(&ident.to_string());
+            }
+            TokenTree::Literal(literal) => {
+                out_string.push_str(&literal.to_string());
+            }
+            TokenTree::Punct(punct) => {
+                let punct_str = match punct.as_char() {
+                    '&' => "_amp_",
+                    '*' => "_star_",
+                    '.' => "_dot_",
+                    ',' => "_comma_",
+                    '#' => "_hash_",
+                    '@' => "_at_",
+                    '!' => "_bang_",
+                    '$' => "_dollar_",
+                    '%' => "_pct_",
+                    '^' => "_caret_",
+                    '<' => "_lt_",
+                    '>' => "_gt_",
+                    _ => "_punct_"
+                };
+                out_string.push_str(punct_str);
+            }
+            TokenTree::Group(group) => {
+
+                let (open_delim, close_delim) = match group.delimiter() {
+                    Delimiter::Brace => ("_open_curly_", "_close_curly_"),
+                    Delimiter::Parenthesis => ("_open_paren_", "_close_paren_"),
+                    Delimiter::Bracket => ("_open_square_", "_close_square_"),
+                    Delimiter::None => ("_open_none_", "_close_none_"),
+                };
+                let insides = tokens_to_string(group.stream());
+
+                out_string.push_str(open_delim);
+                out_string.push_str(&insides);
+                out_string.push_str(close_delim);
+            }
+
+        }
+    }
+    out_string
+}
+
+fn render_fn_signature(sig: FnSignature) -> Result<TokenStream, SyntaxError> {
+
+    let fn_name = sig.fn_name;
+
+    let generic_tokens = if !sig.generics.is_empty() {
+        let sig_generics = sig.generics;
+        quote! {
+            < #sig_generics >
+        }
+    } else {
+        TokenStream::new()
+    };
+
+    let mut arg_list_tokens = TokenStream::new();
+    for arg in sig.args {
+        if arg.is_mut {
+            arg_list_tokens.extend(quote! { mut });
+        }
+        if let Some(arg_name_ident) = arg.arg_name {
+            arg_list_tokens.extend([TokenTree::Ident(arg_name_ident), TokenTree::Punct(Punct::new(':', Spacing::Alone))]);
+        }
+
+        arg_list_tokens.extend(arg.arg_type);
+        arg_list_tokens.extend([TokenTree::Punct(Punct::new(',', Spacing::Alone))]);
+    }
+    let result_tokens = if !sig.result.is_empty() {
+        let sig_results = sig.result;
+        quote! {
+            -> #sig_results
+        }
+    } else {
+        TokenStream::new()
+    };
+
+    let pub_qualifiers = sig.pub_qualifiers;
+
+    let sig_tokens = quote! {
+        #pub_qualifiers fn #fn_name #generic_tokens (#arg_list_tokens) #result_tokens
+    };
+
+    Ok(sig_tokens)
+}
+
+
+
+// // // // // // // // // // // // // // // // // // // // // // // // // // // // // // // // // // //
+// Reference (example of input and the corresponding output, in a form that's easier to read)
+// // // // // // // // // // // // // // // // // // // // // // // // // // // // // // // // // // //
+
+//Update: Just run `cargo expand`
+
+//=====================================================================================
+// Unit Tests
+//=====================================================================================
+
+//Positive Examples:
+// i32
+// val: i32
+// a: &dyn PrimInt
+// &i32
+// &Vec<&i32>
+// Box<dyn PrimInt>
+// HashMap<String, Box<dyn PrimInt>>
+// mut val: i32
+//
+//Negative Examples:
+// NULL (no tokens)
+// val:
+// HashMap<String
+//
+#[test]
+fn require_fn_arg_test() {
+
+    //Positive Examples:
+    let mut input_tokens_iter = quote! {
+        i32
+    }.into_iter();
+    assert!(require_fn_arg(&mut input_tokens_iter, Span::call_site()).is_ok());
+
+    let mut input_tokens_iter = quote! {
+        val: i32
+    }.into_iter();
+    assert!(require_fn_arg(&mut input_tokens_iter, Span::call_site()).is_ok());
+
+    let mut input_tokens_iter = quote! {
+        mut val: i32
+    }.into_iter();
+    let arg = require_fn_arg(&mut input_tokens_iter, Span::call_site()).unwrap();
+    assert!(arg.is_mut && arg.arg_name.unwrap() == "val");
+
+    let mut input_tokens_iter = quote! {
+        a: &dyn PrimInt
+    }.into_iter();
+    assert!(require_fn_arg(&mut input_tokens_iter, Span::call_site()).is_ok());
+
+    let mut input_tokens_iter = quote! {
+        a: &i32
+    }.into_iter();
+    assert!(require_fn_arg(&mut input_tokens_iter, Span::call_site()).is_ok());
+
+    let mut input_tokens_iter = quote! {
+        a: &Vec<&i32>
+    }.into_iter();
+    assert!(require_fn_arg(&mut input_tokens_iter, Span::call_site()).is_ok());
+
+    let mut input_tokens_iter = quote! {
+        a: Box<dyn PrimInt>
+    }.into_iter();
+    assert!(require_fn_arg(&mut input_tokens_iter, Span::call_site()).is_ok());
+
+    let mut input_tokens_iter = quote! {
+        a: HashMap<String, Box<dyn PrimInt>>
+    }.into_iter();
+    assert!(require_fn_arg(&mut input_tokens_iter, Span::call_site()).is_ok());
+
+    //Negative Examples:
+    let mut input_tokens_iter = quote! {
+        
+    }.into_iter();
+    assert!(require_fn_arg(&mut input_tokens_iter, Span::call_site()).is_err());
+
+    let mut input_tokens_iter = quote! {
+        val:
+    }.into_iter();
+    assert!(require_fn_arg(&mut input_tokens_iter, Span::call_site()).is_err());
+
+    let mut input_tokens_iter = quote! {
+        HashMap<String //Ugg.  This bad syntax screws up my text editor's pretty printer, but the compiler is fine
+    }.into_iter();
+    assert!(require_fn_arg(&mut input_tokens_iter, Span::call_site()).is_err());
+
+}
+
+#[test]
+fn require_fn_signature_test() {
+
+    use quote::{quote};
+    use crate::parse::require_fn_signature;     
+
+    //=====================================================================================
+    //Test that I can parse a basic signature
+    let mut input_tokens_iter = quote! {
+        fn min_max(val: i32, min: &i32, max: &i32);
+    }.into_iter();
+
+    let result_signature = require_fn_signature(&mut input_tokens_iter, true, Span::call_site()).unwrap();
+
+    assert_eq!(result_signature.fn_name, "min_max");
+
+    //=====================================================================================
+    //Next test that I can parse "pub"
+    let mut input_tokens_iter = quote! {
+        pub fn min_max(val: i32, min: &i32, max: &i32) -> Result<i32, String>;
+    }.into_iter();
+
+    let result_signature = require_fn_signature(&mut input_tokens_iter, true, Span::call_site()).unwrap();
+
+    let mut pub_qualifiers_iter = result_signature.pub_qualifiers.into_iter();
+    require_keyword(&mut pub_qualifiers_iter, "pub", Span::call_site()).unwrap();
+    assert!(pub_qualifiers_iter.next().is_none());
+
+    //=====================================================================================
+    //Next test that I can parse pub(crate)
+    let mut input_tokens_iter = quote! {
+        pub(crate) fn min_max(val: i32, min: &i32, max: &i32) -> Result<i32, String>;
+    }.into_iter();
+
+    let result_signature = require_fn_signature(&mut input_tokens_iter, true, Span::call_site()).unwrap();
+
+    let mut pub_qualifiers_iter = result_signature.pub_qualifiers.into_iter();
+    require_keyword(&mut pub_qualifiers_iter, "pub", Span::call_site()).unwrap();
+    require_group(&mut pub_qualifiers_iter, Delimiter::Parenthesis, Span::call_site(), "missing '(crate)'").unwrap();
+    assert!(pub_qualifiers_iter.next().is_none());
+
+    //=====================================================================================
+    //Next, test that I can parse some simple generics
+    let mut input_tokens_iter = quote! {
+        fn min_max<A, B>(val: i32, min: &A, max: &B) -> Result<A, String>;
+    }.into_iter();
+
+    let result_signature = require_fn_signature(&mut input_tokens_iter, true, Span::call_site()).unwrap();
+
+    let mut generics_iter = result_signature.generics.into_iter();
+    let _ = require_ident(&mut generics_iter, Span::call_site()).unwrap();
+    require_punct(&mut generics_iter, ',', Span::call_site()).unwrap();
+    let _ = require_ident(&mut generics_iter, Span::call_site()).unwrap();
+    assert!(generics_iter.next().is_none());
+
+    //=====================================================================================
+    //Next, test that I can handle complicated nested generics
+    let mut input_tokens_iter = quote! {
+        fn min_max<A:From<i32>, B>(val: i32, min: &A, max: &B) -> Result<A, String>;
+    }.into_iter();
+
+    let result_signature = require_fn_signature(&mut input_tokens_iter, true, Span::call_site()).unwrap();
+
+    let mut generics_iter = result_signature.generics.into_iter();
+    let _ = require_ident(&mut generics_iter, Span::call_site()).unwrap();
+    require_punct(&mut generics_iter, ':', Span::call_site()).unwrap();
+    let _ = require_ident(&mut generics_iter, Span::call_site()).unwrap();
+    let _ = require_angle_group(&mut generics_iter, Span::call_site(), "expecting angle brackets").unwrap();
+    require_punct(&mut generics_iter, ',', Span::call_site()).unwrap();
+    let _ = require_ident(&mut generics_iter, Span::call_site()).unwrap();
+    assert!(generics_iter.next().is_none());
+
+    //=====================================================================================
+    //Next, test that I get all the args with names
+    let mut input_tokens_iter = quote! {
+        fn min_max(val: i32, min: &i32, max: &i32);
+    }.into_iter();
+
+    let result_signature = require_fn_signature(&mut input_tokens_iter, true, Span::call_site()).unwrap();
+
+    assert_eq!(result_signature.args.len(), 3);
+    assert!(result_signature.args[0].arg_name.is_some());
+    let mut arg2_type_iter = result_signature.args[2].arg_type.clone().into_iter();
+    require_punct(&mut arg2_type_iter, '&', Span::call_site()).unwrap();
+    let _ = require_ident(&mut arg2_type_iter, Span::call_site()).unwrap();
+    
+    //=====================================================================================
+    //Next, test that I get all the args without names
+    let mut input_tokens_iter = quote! {
+        fn min_max(i32, &i32, &i32);
+    }.into_iter();
+
+    let result_signature = require_fn_signature(&mut input_tokens_iter, true, Span::call_site()).unwrap();
+
+    assert_eq!(result_signature.args.len(), 3);
+    assert!(result_signature.args[0].arg_name.is_none());
+    let mut arg2_type_iter = result_signature.args[2].arg_type.clone().into_iter();
+    require_punct(&mut arg2_type_iter, '&', Span::call_site()).unwrap();
+    let _ = require_ident(&mut arg2_type_iter, Span::call_site()).unwrap();
+
+    //=====================================================================================
+    //Next, test that I can handle no arguments
+    let mut input_tokens_iter = quote! {
+        fn min_max();
+    }.into_iter();
+
+    require_fn_signature(&mut input_tokens_iter, true, Span::call_site()).unwrap();
+
+    //=====================================================================================
+    //Next, test that I can parse a result
+    let mut input_tokens_iter = quote! {
+        fn min_max<A>() -> Result<A, String>;
+    }.into_iter();
+
+    let result_signature = require_fn_signature(&mut input_tokens_iter, true, Span::call_site()).unwrap();
+
+    let mut result_iter = result_signature.result.into_iter();
+    let _ = require_ident(&mut result_iter, Span::call_site()).unwrap();
+    let _ = require_angle_group(&mut result_iter, Span::call_site(), "expecting angle brackets").unwrap();
+}
+
+#[test]
+fn fallback_test() {
+
+    //=====================================================================================
+    //Test that the fallback body is emitted once, no matter how many combinations of types call it
+    let output = double_dyn_internal(quote! {
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB) -> i64 {
+            fallback_marker()
+        }
+
+        impl for <i32, i32>
+        {
+            fn multiply(a: &i32, b: &i32) -> i64 {
+                0
+            }
+        }
+
+        impl for <i64, i64>
+        {
+            fn multiply(a: &i64, b: &i64) -> i64 {
+                0
+            }
+        }
+
+        impl for <u8, u8>
+        {
+            fn multiply(a: &u8, b: &u8) -> i64 {
+                0
+            }
+        }
+    }).unwrap().to_string();
+
+    assert_eq!(output.matches("fallback_marker").count(), 1);
+    assert!(output.contains("fn __double_dyn_fallback_multiply"));
+}
+
+#[test]
+fn exhaustive_test() {
+
+    //=====================================================================================
+    //Test that a function marked "exhaustive" reports the missing combinations of types
+    let output = double_dyn_internal(quote! {
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        #[exhaustive]
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB) -> i64;
+
+        impl for <i32, i32>
+        {
+            fn multiply(a: &i32, b: &i32) -> i64 {
+                *a as i64 * *b as i64
+            }
+        }
+
+        impl for <i32, i64>
+        {
+            fn multiply(a: &i32, b: &i64) -> i64 {
+                *a as i64 * *b
+            }
+        }
+
+        impl for <i64, i32>
+        {
+            fn multiply(a: &i64, b: &i32) -> i64 {
+                *a * *b as i64
+            }
+        }
+    }).unwrap().to_string();
+
+    assert!(output.contains("compile_error"));
+    assert!(output.contains("`multiply` is not implemented for <i64, i64>"));
+
+    //=====================================================================================
+    //Test that the warn mode emits a deprecated item instead of an error, and that it applies to the whole
+    // invocation.  A function with a fallback body covers every combination
+    let output = double_dyn_internal(quote! {
+        #![exhaustive(warn)]
+
+        type A: MyTrait;
+        type B: MyTrait;
+
+        fn multiply(a: &dyn MyTrait, b: &dyn MyTrait) -> i64;
+        fn add(a: &dyn MyTrait, b: &dyn MyTrait) -> i64 {
+            0
+        }
+
+        impl for <i32, i64>
+        {
+            fn multiply(a: &i32, b: &i64) -> i64 {
+                *a as i64 * *b
+            }
+        }
+    }).unwrap().to_string();
+
+    assert!(!output.contains("compile_error"));
+    assert!(output.contains("deprecated"));
+    assert!(output.contains("`multiply` is not implemented for <i32, i32>, <i64, i32>, <i64, i64>"));
+    assert!(!output.contains("`add` is not implemented"));
+
+    //=====================================================================================
+    //Test that an invocation without any "exhaustive" attributes doesn't check
+    let output = double_dyn_internal(quote! {
+        type A: MyTrait;
+        type B: MyTrait;
+
+        fn multiply(a: &dyn MyTrait, b: &dyn MyTrait) -> i64;
+
+        impl for <i32, i64>
+        {
+            fn multiply(a: &i32, b: &i64) -> i64 {
+                *a as i64 * *b
+            }
+        }
+    }).unwrap().to_string();
+
+    assert!(!output.contains("compile_error"));
+    assert!(!output.contains("deprecated"));
+
+    //=====================================================================================
+    //Test that unknown attributes are rejected
+    assert!(double_dyn_internal(quote! {
+        type A: MyTrait;
+        type B: MyTrait;
+
+        #[exhaustive(error)]
+        fn multiply(a: &dyn MyTrait, b: &dyn MyTrait) -> i64;
+
+        impl for <i32, i64>
+        {
+            fn multiply(a: &i32, b: &i64) -> i64 {
+                *a as i64 * *b
+            }
+        }
+    }).is_err());
+}
+
+#[test]
+fn conflicting_impls_test() {
+
+    //=====================================================================================
+    //Test that two impl blocks covering the same combination of types are reported at both blocks
+    let output = double_dyn_internal(quote! {
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB) -> i64;
+
+        impl for <[i32, i64], i32>
+        {
+            fn multiply(a: &i32, b: &i32) -> i64 {
+                0
+            }
+        }
+
+        impl for <i32, i32>
+        {
+            fn multiply(a: &i32, b: &i32) -> i64 {
+                1
+            }
+        }
+    }).unwrap().to_string();
+
+    assert!(output.contains("conflicting implementations for <i32, i32>"));
+    assert!(output.contains("other implementation for <i32, i32> is here"));
+
+    //=====================================================================================
+    //Test that a commutative impl overlapping itself isn't a conflict, but overlapping another block via its
+    // inverse is
+    let output = double_dyn_internal(quote! {
+        type A: MyTrait;
+        type B: MyTrait;
+
+        fn multiply(a: &dyn MyTrait, b: &dyn MyTrait) -> i64;
+
+        #[commutative]
+        impl for <[i32, i64], [i32, i64]>
+        {
+            fn multiply(a: &i32, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).unwrap().to_string();
+
+    assert!(!output.contains("compile_error"));
+
+    let output = double_dyn_internal(quote! {
+        type A: MyTrait;
+        type B: MyTrait;
+
+        fn multiply(a: &dyn MyTrait, b: &dyn MyTrait) -> i64;
+
+        #[commutative]
+        impl for <i32, i64>
+        {
+            fn multiply(a: &i32, b: &i64) -> i64 {
+                0
+            }
+        }
+
+        impl for <i64, i32>
+        {
+            fn multiply(a: &i64, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).unwrap().to_string();
+
+    assert!(output.contains("conflicting implementations for <i64, i32>"));
+
+    //=====================================================================================
+    //Test that wildcard impls with the same number of wildcards conflict where they overlap.  The input is parsed
+    // from a string because quote! would interpolate the #A and #B placeholders
+    let output = double_dyn_internal("
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB) -> i64;
+
+        impl for <i32, _>
+        {
+            fn multiply(a: &i32, b: &#B) -> i64 {
+                0
+            }
+        }
+
+        impl for <_, i64>
+        {
+            fn multiply(a: &#A, b: &i64) -> i64 {
+                1
+            }
+        }
+    ".parse().unwrap()).unwrap().to_string();
+
+    assert!(output.contains("conflicting implementations for <i32, i64>"));
+
+    //=====================================================================================
+    //Test that two override blocks covering the same combination still conflict
+    let output = double_dyn_internal(quote! {
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB) -> i64;
+
+        #[override]
+        impl for <i32, i32>
+        {
+            fn multiply(a: &i32, b: &i32) -> i64 {
+                0
+            }
+        }
+
+        #[override]
+        impl for <i32, i32>
+        {
+            fn multiply(a: &i32, b: &i32) -> i64 {
+                1
+            }
+        }
+    }).unwrap().to_string();
+
+    assert!(output.contains("conflicting implementations for <i32, i32>"));
+}
+
+#[test]
+fn except_test() {
+
+    //=====================================================================================
+    //Test that an exclusion that doesn't match any combination covered by the block is an error
+    assert!(double_dyn_internal(quote! {
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB) -> i64;
+
+        impl for <[i32, i64], i32> except <i32, i64>
+        {
+            fn multiply(a: &i32, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).is_err());
+
+    //=====================================================================================
+    //Test that excluded combinations are reported by the exhaustiveness check
+    let output = double_dyn_internal(quote! {
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        #[exhaustive]
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB) -> i64;
+
+        impl for <[i32, i64], i32> except <i64, i32>
+        {
+            fn multiply(a: &i32, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).unwrap().to_string();
+
+    assert!(output.contains("`multiply` is not implemented for <i64, i32>"));
+}
+
+#[test]
+fn zip_and_diagonal_test() {
+
+    //=====================================================================================
+    //Test that zipped type lists must have the same length
+    assert!(double_dyn_internal(quote! {
+        type A: MyTrait;
+        type B: MyTrait;
+
+        fn widen(a: &dyn MyTrait, b: &dyn MyTrait) -> u64;
+
+        impl for zip <[u8, u16], [u16, u32, u64]>
+        {
+            fn widen(a: &u8, b: &u16) -> u64 {
+                0
+            }
+        }
+    }).is_err());
+
+    //=====================================================================================
+    //Test that a diagonal can be excluded from a block
+    let output = double_dyn_internal(quote! {
+        type A: MyTrait;
+        type B: MyTrait;
+
+        #[exhaustive]
+        fn widen(a: &dyn MyTrait, b: &dyn MyTrait) -> u64;
+
+        impl for <[u8, u16], [u8, u16]> except <T in _, T>
+        {
+            fn widen(a: &u8, b: &u8) -> u64 {
+                0
+            }
+        }
+    }).unwrap().to_string();
+
+    assert!(output.contains("`widen` is not implemented for <u16, u16>, <u8, u8>"));
+}
+
+#[test]
+fn dispatched_arg_kind_test() {
+
+    let trait_name = Ident::new("MyTrait", Span::call_site());
+    let arg = |arg_type: TokenStream| FnArg {
+        arg_name: Some(Ident::new("a", Span::call_site())),
+        is_mut: false,
+        arg_type,
+    };
+
+    assert!(dispatched_arg_kind(&arg(quote!{ &dyn MyTrait }), &trait_name).unwrap() == ArgKind::Ref);
+    assert!(dispatched_arg_kind(&arg(quote!{ &mut dyn MyTrait }), &trait_name).unwrap() == ArgKind::RefMut);
+    assert!(dispatched_arg_kind(&arg(quote!{ Box<dyn MyTrait> }), &trait_name).unwrap() == ArgKind::Boxed);
+    assert!(dispatched_arg_kind(&arg(quote!{ Rc<dyn MyTrait> }), &trait_name).unwrap() == ArgKind::Rc);
+    assert!(dispatched_arg_kind(&arg(quote!{ Arc<dyn MyTrait> }), &trait_name).unwrap() == ArgKind::Arc);
+    assert!(dispatched_arg_kind(&arg(quote!{ &Rc<dyn MyTrait> }), &trait_name).is_err());
+    assert!(dispatched_arg_kind(&arg(quote!{ &dyn OtherTrait }), &trait_name).is_err());
+    assert!(dispatched_arg_kind(&arg(quote!{ Vec<&dyn MyTrait> }), &trait_name).is_err());
+}
+
+#[test]
+fn optional_args_test() {
+
+    //=====================================================================================
+    //Test that only the last dispatched arg may be optional
+    assert!(double_dyn_internal(quote! {
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        fn multiply(a: Option<&dyn MyTraitA>, b: &dyn MyTraitB) -> i64;
+
+        impl for <i32, i32>
+        {
+            fn multiply(a: &i32, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).is_err());
+
+    //=====================================================================================
+    //Test that "None" can't be combined with other types in a list
+    assert!(double_dyn_internal(quote! {
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        fn multiply(a: &dyn MyTraitA, b: Option<&dyn MyTraitB>) -> i64;
+
+        impl for <i32, [i32, None]>
+        {
+            fn multiply(a: &i32, b: Option<&dyn MyTraitB>) -> i64 {
+                0
+            }
+        }
+    }).is_err());
+
+    //=====================================================================================
+    //Test that a function where the arg isn't optional can't be implemented for "None"
+    assert!(double_dyn_internal(quote! {
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB) -> i64;
+
+        impl for <i32, None>
+        {
+            fn multiply(a: &i32, b: Option<&dyn MyTraitB>) -> i64 {
+                0
+            }
+        }
+    }).is_err());
+
+    //=====================================================================================
+    //Test that the exhaustiveness check includes the None case
+    let output = double_dyn_internal(quote! {
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        #[exhaustive]
+        fn multiply(a: &dyn MyTraitA, b: Option<&dyn MyTraitB>) -> i64;
+
+        impl for <i32, i32>
+        {
+            fn multiply(a: &i32, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).unwrap().to_string();
+
+    assert!(output.contains("`multiply` is not implemented for <i32, None>"));
+}
+
+#[test]
+fn reduce_test() {
+
+    //=====================================================================================
+    //Test that a "reduce" function must return a new item of the same trait
+    assert!(double_dyn_internal(quote! {
+        type A: MyTrait;
+        type B: MyTrait;
+
+        #[reduce]
+        fn add(a: &dyn MyTrait, b: &dyn MyTrait) -> i64;
+
+        impl for <i32, i32>
+        {
+            fn add(a: &i32, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).is_err());
+
+    //=====================================================================================
+    //Test that a "reduce" function must use the same trait for both args
+    assert!(double_dyn_internal(quote! {
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        #[reduce]
+        fn add(a: &dyn MyTraitA, b: &dyn MyTraitB) -> Box<dyn MyTraitA>;
+
+        impl for <i32, i32>
+        {
+            fn add(a: &i32, b: &i32) -> Box<dyn MyTraitA> {
+                Box::new(0)
+            }
+        }
+    }).is_err());
+}
+
+#[test]
+fn pairs_test() {
+
+    //=====================================================================================
+    //Test that a "pairs" function can't take args other than the dispatched args
+    assert!(double_dyn_internal(quote! {
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        #[pairs]
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB, scale: i64) -> i64;
+
+        impl for <i32, i32>
+        {
+            fn multiply(a: &i32, b: &i32, scale: i64) -> i64 {
+                0
+            }
+        }
+    }).is_err());
+
+    //=====================================================================================
+    //Test that the helper for pairs within one slice is only emitted when both args use the same trait
+    let output = double_dyn_internal(quote! {
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        #[pairs]
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB) -> i64;
+
+        impl for <i32, i32>
+        {
+            fn multiply(a: &i32, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).unwrap().to_string();
+
+    assert!(output.contains("fn multiply_bipartite_pairs"));
+    assert!(!output.contains("fn multiply_all_pairs"));
+}
+
+#[test]
+fn batch_test() {
+
+    //=====================================================================================
+    //Test that the ordinals are declared by unsafe traits, which are only emitted for a "batch" function
+    let output = double_dyn_internal(quote! {
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        #[batch]
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB) -> i64;
+
+        impl for <i64, i32>
+        {
+            fn multiply(a: &i64, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).unwrap().to_string();
+
+    assert!(output.contains("trait MyTraitA : MyTraitAOrdinal"));
+    assert!(output.contains("unsafe trait MyTraitAOrdinal { fn double_dyn_type_ordinal (& self) -> usize ; }"));
+    assert!(output.contains("unsafe impl MyTraitBOrdinal for i32"));
+
+    let output = double_dyn_internal(quote! {
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB) -> i64;
+
+        impl for <i64, i32>
+        {
+            fn multiply(a: &i64, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).unwrap().to_string();
+
+    assert!(!output.contains("double_dyn_type_ordinal"));
+
+    //=====================================================================================
+    //Test that a "batch" function can't take args other than the dispatched args
+    assert!(double_dyn_internal(quote! {
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        #[batch]
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB, scale: i64) -> i64;
+
+        impl for <i32, i32>
+        {
+            fn multiply(a: &i32, b: &i32, scale: i64) -> i64 {
+                0
+            }
+        }
+    }).is_err());
+
+    //=====================================================================================
+    //Test that a "batch" function can't take owned args
+    assert!(double_dyn_internal(quote! {
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        #[batch]
+        fn multiply(a: Box<dyn MyTraitA>, b: &dyn MyTraitB) -> i64;
+
+        impl for <i32, i32>
+        {
+            fn multiply(a: Box<i32>, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).is_err());
+}
+
+#[test]
+fn table_backend_test() {
+
+    //=====================================================================================
+    //Test that the table backend doesn't emit the trait methods for the functions
+    let output = double_dyn_internal(quote! {
+        #![backend(table)]
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB) -> i64;
+
+        impl for <i64, i32>
+        {
+            fn multiply(a: &i64, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).unwrap().to_string();
+
+    assert!(output.contains("static __DOUBLE_DYN_TABLE"));
+    assert!(!output.contains("l1_multiply"));
+    assert!(!output.contains("l2_multiply"));
+    assert!(output.contains("trait MyTraitA : MyTraitAOrdinal"));
+    assert!(output.contains("unsafe impl MyTraitAOrdinal for i64"));
+    assert!(output.contains("if ordinals . iter () . zip (dims . iter ()) . all"));
+
+    //=====================================================================================
+    //Test that an unknown backend is an error
+    assert!(double_dyn_internal(quote! {
+        #![backend(switch)]
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB) -> i64;
+
+        impl for <i32, i32>
+        {
+            fn multiply(a: &i32, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).is_err());
+}
+
+#[test]
+fn enum_backend_test() {
+
+    //=====================================================================================
+    //Test that the enum backend emits an enum with a variant for each type, in place of the trait
+    let output = double_dyn_internal(quote! {
+        #![backend(enum)]
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB) -> i64;
+
+        impl for <Vec<u8>, i32>
+        {
+            fn multiply(a: &Vec<u8>, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).unwrap().to_string();
+
+    assert!(output.contains("enum MyTraitA { VecU8 (Vec < u8 >) , }"));
+    assert!(!output.contains("trait MyTraitA"));
+
+    //=====================================================================================
+    //Test that the enum backend doesn't support owned args
+    assert!(double_dyn_internal(quote! {
+        #![backend(enum)]
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        fn multiply(a: Box<dyn MyTraitA>, b: &dyn MyTraitB) -> i64;
+
+        impl for <i32, i32>
+        {
+            fn multiply(a: Box<i32>, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).is_err());
+}
+
+#[test]
+fn registry_backend_test() {
+
+    //=====================================================================================
+    //Test that the registry backend emits a registry static, and only the "as_any" method in the trait
+    let output = double_dyn_internal(quote! {
+        #![backend(registry)]
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB) -> i64;
+
+        impl for <i64, i32>
+        {
+            fn multiply(a: &i64, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).unwrap().to_string();
+
+    assert!(output.contains("struct MultiplyRegistry"));
+    assert!(output.contains("static MULTIPLY : MultiplyRegistry"));
+    assert!(output.contains("fn double_dyn_as_any"));
+    assert!(!output.contains("l1_multiply"));
+    assert!(!output.contains("double_dyn_type_ordinal"));
+
+    //=====================================================================================
+    //Test that the registry backend requires exactly two "&dyn" args
+    assert!(double_dyn_internal(quote! {
+        #![backend(registry)]
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        fn multiply(a: &mut dyn MyTraitA, b: &dyn MyTraitB) -> i64;
+
+        impl for <i32, i32>
+        {
+            fn multiply(a: &mut i32, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).is_err());
+
+    assert!(double_dyn_internal(quote! {
+        #![backend(registry)]
+        type A: MyTraitA;
+        type B: MyTraitB;
+        type C: MyTraitC;
+
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB, c: &dyn MyTraitC) -> i64;
+
+        impl for <i32, i32, i32>
+        {
+            fn multiply(a: &i32, b: &i32, c: &i32) -> i64 {
+                0
+            }
+        }
+    }).is_err());
+}
+
+#[test]
+fn static_dispatch_test() {
+
+    //=====================================================================================
+    //Test that a "static_dispatch" function emits a trait that is implemented for each combination of types
+    let output = double_dyn_internal(quote! {
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        #[static_dispatch]
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB) -> i64;
+
+        impl for <i64, i32>
+        {
+            fn multiply(a: &i64, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).unwrap().to_string();
+
+    assert!(output.contains("trait MultiplyWith < __DoubleDynB >"));
+    assert!(output.contains("impl MultiplyWith < i32 > for i64"));
+
+    //=====================================================================================
+    //Test that a "static_dispatch" function can't take an optional arg
+    assert!(double_dyn_internal(quote! {
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        #[static_dispatch]
+        fn multiply(a: &dyn MyTraitA, b: Option<&dyn MyTraitB>) -> i64;
+
+        impl for <i32, i32>
+        {
+            fn multiply(a: &i32, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).is_err());
+}
+
+#[test]
+fn methods_test() {
+
+    //=====================================================================================
+    //Test that the "methods" attribute replaces a trait and an impl with callbacks
+    let output = methods_internal(quote!{}, quote! {
+        pub trait MyTraitA: Debug {
+            fn area(&self) -> f32;
+        }
+    }).unwrap().to_string();
+
+    assert!(output.contains("macro_rules ! __double_dyn_trait_MyTraitA"));
+    assert!(output.contains("pub trait MyTraitA : Debug { fn area (& self) -> f32 ; $ ($ methods) * }"));
+    assert!(output.contains("pub trait MyTraitA : Debug where Self : $ supertrait {"));
+
+    let output = methods_internal(quote!{}, quote! {
+        impl MyTraitA for Vec<u8> {
+            fn area(&self) -> f32 {
+                0.0
+            }
+        }
+    }).unwrap().to_string();
+
+    assert!(output.contains("macro_rules ! __double_dyn_impl_MyTraitA_vec_lt_u8_gt"));
+    assert!(output.contains("impl MyTraitA for Vec < u8 >"));
+
+    //=====================================================================================
+    //Test that the "methods" attribute rejects an inherent impl
+    assert!(methods_internal(quote!{}, quote! {
+        impl Vec<u8> {
+            fn area(&self) -> f32 {
+                0.0
+            }
+        }
+    }).is_err());
+
+    //=====================================================================================
+    //Test that an invocation passes the methods of an extended trait to its callbacks
+    let output = double_dyn_internal(quote! {
+        #[methods]
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB) -> i64;
+
+        impl for <i64, i32>
+        {
+            fn multiply(a: &i64, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).unwrap().to_string();
+
+    assert!(output.contains("__double_dyn_trait_MyTraitA ! {"));
+    assert!(output.contains("__double_dyn_impl_MyTraitA_i64 ! {"));
+    assert!(output.contains("trait MyTraitB"));
+
+    //=====================================================================================
+    //Test that the bounds of an extended trait can't be declared in the invocation
+    assert!(double_dyn_internal(quote! {
+        #[methods]
+        type A: MyTraitA: Debug;
+        type B: MyTraitB;
+
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB) -> i64;
+
+        impl for <i64, i32>
+        {
+            fn multiply(a: &i64, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).is_err());
+}
+
+#[test]
+fn upcast_test() {
+
+    //=====================================================================================
+    //Test that an "upcast" trait gets a bridge trait with a blanket impl
+    let output = double_dyn_internal(quote! {
+        #[upcast]
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB) -> i64;
+
+        impl for <i64, i32>
+        {
+            fn multiply(a: &i64, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).unwrap().to_string();
+
+    assert!(output.contains("trait AsMyTraitA : MyTraitA"));
+    assert!(output.contains("fn as_my_trait_a (& self) -> & dyn MyTraitA"));
+    assert!(output.contains("impl < T : MyTraitA > AsMyTraitA for T"));
+    assert!(!output.contains("AsMyTraitB"));
+
+    //=====================================================================================
+    //Test that the enum backend doesn't support "upcast" traits
+    assert!(double_dyn_internal(quote! {
+        #![backend(enum)]
+        #[upcast]
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB) -> i64;
+
+        impl for <i64, i32>
+        {
+            fn multiply(a: &i64, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).is_err());
+}
+
+#[test]
+fn decl_test() {
+
+    //=====================================================================================
+    //Test that "double_dyn_decl!" emits a callback that passes the impls to "double_dyn!"
+    let output = double_dyn_decl_internal(quote! {
+        macro factors;
+
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB) -> i64;
+    }).unwrap().to_string();
+
+    assert!(output.contains("macro_rules ! factors"));
+    assert!(output.contains(":: double_dyn :: double_dyn ! { type A : MyTraitA ;"));
+    assert!(output.contains("pub (crate) use factors ;"));
+
+    //=====================================================================================
+    //Test that "double_dyn_impl!" invokes the callback by path
+    let output = double_dyn_impl_internal(quote! {
+        crate::ops::factors;
+
+        impl for <i64, i32>
+        {
+            fn multiply(a: &i64, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).unwrap().to_string();
+
+    assert!(output.starts_with("crate :: ops :: factors ! { impl for < i64 , i32 >"));
+
+    //=====================================================================================
+    //Test that a "collect" attribute emits a macro that passes the impls on to a later site
+    let output = double_dyn_impl_internal(quote! {
+        crate::ops::factors;
+        #![collect(int_factors)]
+
+        impl for <i64, i32>
+        {
+            fn multiply(a: &i64, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).unwrap().to_string();
+
+    assert!(output.contains("macro_rules ! int_factors"));
+    assert!(output.contains(":: double_dyn :: double_dyn_impl ! { $ crate :: ops :: factors ; impl for < i64 , i32 >"));
+    assert!(output.contains("pub (crate) use int_factors ;"));
+
+    //=====================================================================================
+    //Test that a "collect" attribute requires an absolute path
+    assert!(double_dyn_impl_internal(quote! {
+        super::factors;
+        #![collect(int_factors)]
+        impl for <i64, i32> {}
+    }).is_err());
+
+    //=====================================================================================
+    //Test that "double_dyn_impl!" requires a path
+    assert!(double_dyn_impl_internal(quote! {
+        ;
+        impl for <i64, i32> {}
+    }).is_err());
+}
+
+#[test]
+fn extension_test() {
+
+    //=====================================================================================
+    //Test that an extensible family gives the B trait a method that takes the A arg as a trait object
+    let output = double_dyn_internal(quote! {
+        #![extensible]
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB) -> i64;
+
+        impl for <i64, i32>
+        {
+            fn multiply(a: &i64, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).unwrap().to_string();
+
+    assert!(output.contains("fn double_dyn_as_any (& self) -> & dyn :: std :: any :: Any ;"));
+    assert!(output.contains("fn l2_multiply_dyn (& self , a : & dyn MyTraitA ,)"));
+    assert!(output.contains("fn l2_multiply_i64 (& self , a : & i64 ,) -> :: std :: result :: Result < i64 , :: double_dyn :: DispatchError > { self . l2_multiply_dyn (a) }"));
+
+    //=====================================================================================
+    //Test that an extension only implements the traits for its own types
+    let output = double_dyn_internal(quote! {
+        #![extend(shapes, [u8])]
+        #![extensible]
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB) -> i64;
+
+        impl for <u8, i32>
+        {
+            fn multiply(a: &u8, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).unwrap().to_string();
+
+    assert!(output.starts_with("const _ : () = { use shapes :: * ;"));
+    assert!(output.contains("impl MyTraitA for u8"));
+    assert!(output.contains("downcast_ref :: < i32 >"));
+    assert!(!output.contains("impl MyTraitB"));
+    assert!(!output.contains("fn multiply"));
+
+    //=====================================================================================
+    //Test that every combination in an extension must include one of its own types
+    assert!(double_dyn_internal(quote! {
+        #![extend(shapes, [u8])]
+        #![extensible]
+        type A: MyTraitA;
+        type B: MyTraitA;
+
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitA) -> i64;
+
+        impl for <u8, i32>
+        {
+            fn multiply(a: &u8, b: &i32) -> i64 {
+                0
+            }
+        }
+
+        impl for <i64, i32>
+        {
+            fn multiply(a: &i64, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).is_err());
+
+    //=====================================================================================
+    //Test that an extension requires an extensible family
+    assert!(double_dyn_internal(quote! {
+        #![extend(shapes, [u8])]
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB) -> i64;
+
+        impl for <u8, i32>
+        {
+            fn multiply(a: &u8, b: &i32) -> i64 {
+                0
+            }
+        }
+    }).is_err());
+}
@@ -1,9 +1,9 @@
 
 #![allow(dead_code)]
 
-/// Some parts of this file are based on code from seq-macro, by dtolnay.  https://github.com/dtolnay/seq-macro
-/// Included under the terms of the MIT License
-/// 
+// Some parts of this file are based on code from seq-macro, by dtolnay.  https://github.com/dtolnay/seq-macro
+// Included under the terms of the MIT License
+//
 
 use proc_macro2::token_stream::IntoIter as TokenIter;
 use proc_macro2::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
@@ -101,10 +101,9 @@ pub(crate) fn if_ident(iter: &TokenIter) -> Result<bool, SyntaxError> {
 
 //Searches a TokenIter for a contiguous sequence of tokens
 pub(crate) fn if_contains_tokens(iter: &TokenIter, sequence: TokenIter) -> Result<bool, SyntaxError> {
-    let mut new_iter = iter.clone();
     let mut seq_iter = sequence.clone();
     let mut seq_next = seq_iter.next();
-    while let Some(token) = new_iter.next() {
+    for token in iter.clone() {
         match seq_next {
             Some(seq_next_tok) => {
                 if token.to_string() == seq_next_tok.to_string() {
@@ -126,25 +125,15 @@ pub(crate) fn if_contains_tokens(iter: &TokenIter, sequence: TokenIter) -> Resul
 
 //Searches a TokenIter for a contiguous sequence of tokens specified by their string values
 pub(crate) fn if_contains_sequence(iter: &TokenIter, sequence: &[&str]) -> Result<bool, SyntaxError> {
-    let mut new_iter = iter.clone();
     let mut sequence_idx = 0;
-    while let Some(token) = new_iter.next() {
+    for token in iter.clone() {
         match token {
-            TokenTree::Ident(ident) => {
-                if sequence[sequence_idx] == ident.to_string() { //Panic is ok because it means we got bad args
-                    sequence_idx += 1;
-                } else {
-                    //We've found an interruption in the sequence, so start over
-                    sequence_idx = 0;
-                }
+            //Panic is ok if we index past the end of the sequence because it means we got bad args
+            TokenTree::Ident(ident) if ident == sequence[sequence_idx] => {
+                sequence_idx += 1;
             }
-            TokenTree::Punct(punct) => {
-                if sequence[sequence_idx] == punct.to_string() { //Panic is ok because it means we got bad args
-                    sequence_idx += 1;
-                } else {
-                    //We've found an interruption in the sequence, so start over
-                    sequence_idx = 0;
-                }
+            TokenTree::Punct(punct) if punct.as_char().to_string() == sequence[sequence_idx] => {
+                sequence_idx += 1;
             },
             _ => {
                 //We've found an interruption in the sequence, so start over
@@ -167,13 +156,7 @@ pub(crate) fn require_ident(iter: &mut TokenIter, err_span : Span) -> Result<Ide
 
 pub(crate) fn if_keyword(iter: &mut TokenIter, keyword: &str) -> Result<bool, SyntaxError> {
     match iter.clone().next() {
-        Some(TokenTree::Ident(ident)) => {
-            if ident.to_string() == keyword {
-                Ok(true)
-            } else {
-                Ok(false)
-            }
-        }
+        Some(TokenTree::Ident(ident)) => Ok(ident == keyword),
         _ => Ok(false),
     }
 }
@@ -181,7 +164,7 @@ pub(crate) fn if_keyword(iter: &mut TokenIter, keyword: &str) -> Result<bool, Sy
 pub(crate) fn require_keyword(iter: &mut TokenIter, keyword: &str, err_span : Span) -> Result<(), SyntaxError> {
     let token = next_token(iter, err_span)?;
     if let TokenTree::Ident(ident) = &token {
-        if ident.to_string() == keyword {
+        if ident == keyword {
             return Ok(());
         }
     }
@@ -280,6 +263,61 @@ pub(crate) fn require_group(iter: &mut TokenIter, delimiter: Delimiter, err_span
     }
 }
 
+//Parses an attribute, e.g. "#[commutative]", an attribute with args, e.g. "#[exhaustive(warn)]", or an inner
+// attribute, e.g. "#![exhaustive]"
+#[derive(Clone, Debug)]
+pub(crate) struct Attribute {
+    pub inner: bool,
+    pub name: Ident,
+    pub args: Option<Group>,
+    pub span: Span,
+}
+
+pub(crate) fn require_attribute(iter: &mut TokenIter, err_span: Span) -> Result<Attribute, SyntaxError> {
+    require_punct(iter, '#', err_span)?;
+    let inner = if if_punct(iter, '!')? {
+        require_punct(iter, '!', err_span)?;
+        true
+    } else {
+        false
+    };
+    let attrib_group = require_group(iter, Delimiter::Bracket, err_span, "expected square brackets")?;
+    let mut attrib_token_iter = attrib_group.stream().into_iter();
+    let name = require_ident(&mut attrib_token_iter, attrib_group.span())?;
+    let args = if if_group(&mut attrib_token_iter, Delimiter::Parenthesis)? {
+        Some(require_group(&mut attrib_token_iter, Delimiter::Parenthesis, attrib_group.span(), "expected attribute args")?)
+    } else {
+        None
+    };
+    require_end(&mut attrib_token_iter)?;
+
+    Ok(Attribute {
+        inner,
+        name,
+        args,
+        span: attrib_group.span(),
+    })
+}
+
+//Checks whether the next attribute is an inner attribute, e.g. "#![exhaustive]"
+pub(crate) fn if_inner_attribute(iter: &TokenIter) -> Result<bool, SyntaxError> {
+    let mut peek_iter = iter.clone();
+    if !if_punct(&peek_iter, '#')? {
+        return Ok(false);
+    }
+    peek_iter.next();
+    if_punct(&peek_iter, '!')
+}
+
+//Parses all of the attributes up to the next item
+pub(crate) fn require_attributes(iter: &mut TokenIter, err_span: Span) -> Result<Vec<Attribute>, SyntaxError> {
+    let mut attributes = vec![];
+    while if_punct(iter, '#')? {
+        attributes.push(require_attribute(iter, err_span)?);
+    }
+    Ok(attributes)
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct AngleGroup {
     pub open_bracket: Punct,
@@ -353,7 +391,7 @@ pub(crate) fn if_end(iter: &TokenIter) -> Result<bool, SyntaxError> {
 //Iterates over all tokens in a TokenIter, descending into nested TokenTree:Group tokens in a depth-first manner
 pub(crate) fn recursive_scan<F : FnMut(TokenTree, &mut TokenStream) -> Result<(), String>>(iter: &mut TokenIter, func: &mut F) -> Result<TokenStream, SyntaxError> {
     let mut new_stream = TokenStream::new();
-    while let Some(token) = iter.next() {
+    for token in iter {
         match token {
             TokenTree::Group(group) => {
                 let mut inner_iter = group.stream().into_iter();
@@ -544,25 +582,25 @@ pub(crate) fn require_fn_signature(iter: &mut TokenIter, expect_semicolon: bool,
     //First see if we have any visibility qualifiers, i.e. pub, pub(crate), etc.
     let mut pub_qualifiers = TokenStream::new();
     if if_keyword(iter, "pub")? {
-        require_keyword(iter, "pub", err_span.clone())?;
-        pub_qualifiers.extend([TokenTree::Ident(Ident::new("pub", err_span.clone()))]);
+        require_keyword(iter, "pub", err_span)?;
+        pub_qualifiers.extend([TokenTree::Ident(Ident::new("pub", err_span))]);
 
         //See if we have any additional qualifiers in parentheses, e.g. "(crate)"
         if if_group(iter, Delimiter::Parenthesis)? {
-            let group = require_group(iter, Delimiter::Parenthesis, err_span.clone(), "expected visibility qualifier, e.g. \"(crate)\"")?;
+            let group = require_group(iter, Delimiter::Parenthesis, err_span, "expected visibility qualifier, e.g. \"(crate)\"")?;
             pub_qualifiers.extend([TokenTree::Group(group)]);
         }
     }
 
     //The fn keyword
-    require_keyword(iter, "fn", err_span.clone())?;
+    require_keyword(iter, "fn", err_span)?;
 
     //Next parse the function name
-    let fn_name = require_ident(iter, err_span.clone())?;
+    let fn_name = require_ident(iter, err_span)?;
 
     //Next parse the generics, if we have any
     let generics = if if_punct(iter, '<')? {
-        let angle_group = require_angle_group(iter, err_span.clone(), "expecting angle brackets")?;
+        let angle_group = require_angle_group(iter, err_span, "expecting angle brackets")?;
         angle_group.interior_tokens
     } else {
         TokenStream::new()
@@ -570,25 +608,25 @@ pub(crate) fn require_fn_signature(iter: &mut TokenIter, expect_semicolon: bool,
 
     //Now move on to parsing the args
     let mut args = vec![];
-    let args_group = require_group(iter, Delimiter::Parenthesis, err_span.clone(), "expected function args")?;
+    let args_group = require_group(iter, Delimiter::Parenthesis, err_span, "expected function args")?;
     let mut args_group_iter = args_group.stream().into_iter();
     while args_group_iter.clone().next().is_some() {
-        let next_arg = require_fn_arg(&mut args_group_iter, err_span.clone())?;
+        let next_arg = require_fn_arg(&mut args_group_iter, err_span)?;
         args.push(next_arg);
     }
 
     //Now parse the function's return type, if there is one
     let result = if if_punct(iter, '-')? {
-        require_punct(iter, '-', err_span.clone())?;
-        require_punct(iter, '>', err_span.clone())?;
-        require_type(iter, err_span.clone())?
+        require_punct(iter, '-', err_span)?;
+        require_punct(iter, '>', err_span)?;
+        require_type(iter, err_span)?
     } else {
         TokenStream::new()
     };
 
     //If the expect_semicolon arg was passed then the last token must be a semicolon (';')
     if expect_semicolon {
-        require_punct(iter, ';', err_span.clone())?;
+        require_punct(iter, ';', err_span)?;
     }
     
     let new_sig = FnSignature {
@@ -605,6 +643,7 @@ pub(crate) fn require_fn_signature(iter: &mut TokenIter, expect_semicolon: bool,
 #[derive(Clone, Debug)]
 pub(crate) struct FnArg {
     pub arg_name: Option<Ident>,
+    pub is_mut: bool, //If the arg name is preceded by "mut"
     pub arg_type: TokenStream
 }
 
@@ -613,6 +652,7 @@ pub(crate) struct FnArg {
 //Positive Examples:
 // i32
 // val: i32
+// mut val: i32
 // a: &dyn PrimInt
 // &i32
 // &Vec<&i32>
@@ -626,12 +666,26 @@ pub(crate) struct FnArg {
 //
 pub(crate) fn require_fn_arg(iter: &mut TokenIter, err_span : Span) -> Result<FnArg, SyntaxError> {
 
+    //See if we have "mut ident:", because that means we have a mutable binding with a name
+    let mut is_mut = false;
+    if if_keyword(iter, "mut")? {
+        let mut temp_iter = iter.clone();
+        temp_iter.next();
+        if if_ident(&temp_iter)? {
+            temp_iter.next();
+            if if_punct(&temp_iter, ':')? {
+                iter.next();
+                is_mut = true;
+            }
+        }
+    }
+
     //See if we have "ident:", because that means we have a name.
     let arg_name = if if_ident(iter)? {
 
         let mut temp_iter = iter.clone();
-        let arg_name_ident = require_ident(&mut temp_iter, err_span.clone())?;
-        if if_punct(&mut temp_iter, ':')? {
+        let arg_name_ident = require_ident(&mut temp_iter, err_span)?;
+        if if_punct(&temp_iter, ':')? {
             //We already have the arg_name, so just pop two tokens off the iter
             iter.next();
             iter.next();
@@ -644,16 +698,17 @@ pub(crate) fn require_fn_arg(iter: &mut TokenIter, err_span : Span) -> Result<Fn
     };
 
     //Interpret all the remaining tokens as the arg_type until we get to a ',' or the end
-    let arg_type = require_type(iter, err_span.clone())?;
+    let arg_type = require_type(iter, err_span)?;
 
     //If we're in a comma-separated list, then eat the trailing comma
     if if_punct(iter, ',')? {
-        require_punct(iter, ',', err_span.clone())?;
+        require_punct(iter, ',', err_span)?;
     }
 
     //That should be all for this arg
     let new_arg = FnArg{
         arg_name,
+        is_mut,
         arg_type
     };
 
@@ -668,14 +723,14 @@ pub(crate) fn require_type(iter: &mut TokenIter, err_span : Span) -> Result<Toke
     while let Some(token) = iter.clone().next() {
         match token {
             TokenTree::Ident(_) => {
-                let popped_ident = require_ident(iter, err_span.clone())?;
+                let popped_ident = require_ident(iter, err_span)?;
                 type_tokens.extend([TokenTree::Ident(popped_ident)]);
                 found_arg_type_name = true;
             },
             TokenTree::Punct(punct) => {
                 match punct.as_char() {
                     '<' => {
-                        let angle_group = require_angle_group(iter, err_span.clone(), "")?;
+                        let angle_group = require_angle_group(iter, err_span, "")?;
                         type_tokens.extend([TokenTree::Punct(angle_group.open_bracket)]);
                         type_tokens.extend(angle_group.interior_tokens);
                         type_tokens.extend([TokenTree::Punct(angle_group.close_bracket)]);        
@@ -706,7 +761,7 @@ pub(crate) fn require_type(iter: &mut TokenIter, err_span : Span) -> Result<Toke
     //If we didn't find at least one identifier token then the type is invalid
     if !found_arg_type_name {
         return Err(SyntaxError {
-            message: format!("expected type identifier"),
+            message: "expected type identifier".to_string(),
             span: new_err_span,
         });
     }
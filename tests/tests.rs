@@ -186,4 +186,1404 @@ fn arg_position_inference_test() {
     
     let val = multiply(&2, &3, &5, &7, &11);
     assert_eq!(format!("{}", val), "21");
-}
\ No newline at end of file
+}
+
+#[test]
+fn triple_traits_test() {
+
+    double_dyn!{
+        type A: Actor;
+        type B: Target;
+        type C: Tool;
+
+        fn interact(actor: &dyn Actor, target: &dyn Target, tool: &dyn Tool) -> String;
+
+        impl for <[u8, u16], String, [f32, f64]>
+        {
+            fn interact(actor: &#A, target: &#B, tool: &#C) -> String {
+                format!("{} {} {}", actor, target, tool)
+            }
+        }
+
+        impl for <u8, i32, f32>
+        {
+            fn interact(actor: &u8, target: &i32, tool: &f32) -> String {
+                format!("{}", *actor as f32 * *target as f32 * tool)
+            }
+        }
+    }
+
+    let val = interact(&1u8, &"hit".to_string(), &2.5f32);
+    assert_eq!(val, "1 hit 2.5");
+
+    let val = interact(&3u16, &"hit".to_string(), &0.5f64);
+    assert_eq!(val, "3 hit 0.5");
+
+    let val = interact(&2u8, &3, &1.5f32);
+    assert_eq!(val, "9");
+}
+
+#[test]
+fn triple_one_trait_test() {
+
+    double_dyn!{
+        type A: MyTrait: std::fmt::Display;
+        type B: MyTrait;
+        type C: MyTrait;
+
+        fn sum(scale: i32, a: &dyn MyTrait, b: &dyn MyTrait, c: &dyn MyTrait) -> Box<dyn MyTrait>;
+
+        impl for <i32, i32, i32>
+        {
+            fn sum(scale: i32, a: &i32, b: &i32, c: &i32) -> Box<dyn MyTrait> {
+                Box::new(scale * (a + b + c))
+            }
+        }
+
+        #[commutative]
+        impl for <i32, i32, f32>
+        {
+            fn sum(scale: i32, a: &#A, b: &#B, c: &#C) -> Box<dyn MyTrait> {
+                Box::new(scale as f32 * (*a as f32 + *b as f32 + *c as f32))
+            }
+        }
+    }
+
+    let val = sum(2, &1, &2, &3);
+    assert_eq!(format!("{}", val), "12");
+
+    let val = sum(2, &1, &2, &0.5);
+    assert_eq!(format!("{}", val), "7");
+
+    let val = sum(2, &0.5, &1, &2);
+    assert_eq!(format!("{}", val), "7");
+
+    let val = sum(1, &1, &0.5, &2);
+    assert_eq!(format!("{}", val), "3.5");
+}
+
+#[test]
+fn fallback_test() {
+
+    double_dyn!{
+        type A: MyTrait: std::fmt::Display;
+        type B: MyTrait;
+
+        fn describe(a: &dyn MyTrait, b: &dyn MyTrait) -> String {
+            format!("{} and {}", a, b)
+        }
+        fn multiply(a: &dyn MyTrait, b: &dyn MyTrait) -> Box<dyn MyTrait>;
+
+        impl for <i32, i32>
+        {
+            fn describe(a: &i32, b: &i32) -> String {
+                format!("{} and {} are both ints", a, b)
+            }
+
+            fn multiply(a: &i32, b: &i32) -> Box<dyn MyTrait> {
+                Box::new(a * b)
+            }
+        }
+
+        #[commutative]
+        impl for <i32, f32>
+        {
+            fn multiply(a: &#A, b: &#B) -> Box<dyn MyTrait> {
+                Box::new(*a as f32 * *b as f32)
+            }
+        }
+
+        impl for <f32, f32>
+        {
+            fn multiply(a: &f32, b: &f32) -> Box<dyn MyTrait> {
+                Box::new(a * b)
+            }
+        }
+    }
+
+    assert_eq!(describe(&2, &7), "2 and 7 are both ints");
+    assert_eq!(describe(&2, &7.5), "2 and 7.5");
+    assert_eq!(describe(&2.5, &7.5), "2.5 and 7.5");
+
+    let val = multiply(&2.0, &7);
+    assert_eq!(format!("{}", describe(&*val, &1)), "14 and 1");
+}
+
+#[test]
+fn try_variant_test() {
+
+    double_dyn!{
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB) -> i64;
+
+        impl for <[i32, i64], i32>
+        {
+            fn multiply(a: &#A, b: &#B) -> i64 {
+                *a as i64 * *b as i64
+            }
+        }
+
+        impl for <i32, String> {
+            fn multiply(a: &i32, b: &String) -> i64 {
+                if b.is_empty() {
+                    return 0;
+                }
+                *a as i64 * b.parse::<i64>().unwrap()
+            }
+        }
+    }
+
+    assert_eq!(try_multiply(&2i64, &7), Ok(14));
+    assert_eq!(try_multiply(&2, &"".to_string()), Ok(0));
+
+    let err = try_multiply(&2i64, &"7".to_string()).unwrap_err();
+    assert_eq!(err.fn_name, "multiply");
+    assert_eq!(err.type_names, vec!["i64", "alloc::string::String"]);
+    assert_eq!(format!("{}", err), "no implementation of `multiply` for (i64, alloc::string::String)");
+
+    let result = std::panic::catch_unwind(|| multiply(&2i64, &"7".to_string()));
+    assert!(result.is_err());
+}
+
+#[test]
+fn self_type_test() {
+
+    double_dyn!{
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        fn describe(a: &dyn MyTraitA, b: &dyn MyTraitB) -> String;
+
+        //"Self" is the type of the last dispatched arg
+        impl for <i32, String> {
+            fn describe(a: &i32, b: &String) -> String {
+                let suffix: Self = b.clone();
+                format!("{} {}", a, Self::from("x") + &suffix)
+            }
+        }
+    }
+
+    assert_eq!(describe(&2, &"y".to_string()), "2 xy");
+}
+
+#[test]
+fn introspection_test() {
+
+    double_dyn!{
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        fn multiply(a: &dyn MyTraitA, b: &dyn MyTraitB) -> i64;
+        fn describe(a: &dyn MyTraitA, b: &dyn MyTraitB) -> String {
+            format!("{} and {}", a.double_dyn_type_name(), b.double_dyn_type_name())
+        }
+
+        impl for <[i32, i64], i32>
+        {
+            fn multiply(a: &#A, b: &#B) -> i64 {
+                *a as i64 * *b as i64
+            }
+        }
+
+        impl for <i32, Vec<u8>>
+        {
+            fn multiply(a: &i32, b: &Vec<u8>) -> i64 {
+                *a as i64 * b.len() as i64
+            }
+        }
+    }
+
+    assert!(can_multiply(&2, &7));
+    assert!(can_multiply(&2i64, &7));
+    assert!(can_multiply(&2, &vec![1u8]));
+    assert!(!can_multiply(&2i64, &vec![1u8]));
+    assert!(can_describe(&2i64, &vec![1u8]));
+    assert_eq!(describe(&2i64, &vec![1u8]), "i64 and Vec<u8>");
+
+    let impls: Vec<(&str, &[&str])> = MY_TRAIT_A_IMPLS.iter().map(|info| (info.fn_name, info.type_names)).collect();
+    assert_eq!(impls, vec![
+        ("multiply", &["i32", "Vec<u8>"][..]),
+        ("multiply", &["i32", "i32"][..]),
+        ("multiply", &["i64", "i32"][..]),
+    ]);
+}
+
+#[test]
+fn exhaustive_test() {
+
+    double_dyn!{
+        #![exhaustive]
+
+        type A: MyTrait;
+        type B: MyTrait;
+
+        fn multiply(a: &dyn MyTrait, b: &dyn MyTrait) -> i64;
+        #[exhaustive(warn)]
+        fn subtract(a: &dyn MyTrait, b: &dyn MyTrait) -> i64;
+        fn describe(a: &dyn MyTrait, b: &dyn MyTrait) -> String {
+            format!("{} and {}", a.double_dyn_type_name(), b.double_dyn_type_name())
+        }
+
+        #[commutative]
+        impl for <i32, [i8, i64]>
+        {
+            fn multiply(a: &#A, b: &#B) -> i64 {
+                *a as i64 * *b as i64
+            }
+            fn subtract(a: &#A, b: &#B) -> i64 {
+                *a as i64 - *b as i64
+            }
+        }
+
+        impl for <[i8, i64], [i8, i64]>
+        {
+            fn multiply(a: &#A, b: &#B) -> i64 {
+                *a as i64 * *b as i64
+            }
+            fn subtract(a: &#A, b: &#B) -> i64 {
+                *a as i64 - *b as i64
+            }
+        }
+
+        impl for <i32, i32>
+        {
+            fn multiply(a: &i32, b: &i32) -> i64 {
+                *a as i64 * *b as i64
+            }
+            fn subtract(a: &i32, b: &i32) -> i64 {
+                *a as i64 - *b as i64
+            }
+        }
+    }
+
+    assert_eq!(multiply(&2i8, &7i64), 14);
+    assert_eq!(subtract(&7i32, &2i8), 5);
+    assert_eq!(describe(&7i32, &2i8), "i32 and i8");
+}
+
+#[test]
+fn override_test() {
+
+    double_dyn!{
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        fn describe(a: &dyn MyTraitA, b: &dyn MyTraitB) -> String;
+
+        //The override wins, even though it comes before the broader impl
+        #[override]
+        impl for <i32, String>
+        {
+            fn describe(a: &i32, b: &String) -> String {
+                format!("{} {}", a, b)
+            }
+        }
+
+        impl for <[i8, i32, i64], [i32, String]>
+        {
+            fn describe(_a: &#A, _b: &#B) -> String {
+                "anything".to_string()
+            }
+        }
+
+        #[override]
+        impl for <i64, i32>
+        {
+            fn describe(a: &i64, b: &i32) -> String {
+                format!("{}", a * *b as i64)
+            }
+        }
+    }
+
+    assert_eq!(describe(&3, &"apples".to_string()), "3 apples");
+    assert_eq!(describe(&3i64, &7), "21");
+    assert_eq!(describe(&3i8, &7), "anything");
+    assert_eq!(describe(&3i64, &"apples".to_string()), "anything");
+}
+
+#[test]
+fn wildcard_test() {
+
+    double_dyn!{
+        type A: MyTraitA;
+        type B: MyTraitB;
+
+        fn describe(a: &dyn MyTraitA, b: &dyn MyTraitB) -> String;
+
+        impl for <i32, _>
+        {
+            fn describe(a: &i32, b: &#B) -> String {
+                format!("{} with {}", a, b.double_dyn_type_name())
+            }
+        }
+
+        impl for <_, String>
+        {
+            fn describe(a: &#A, b: &String) -> String {
+                format!("{} with {}", a.double_dyn_type_name(), b)
+            }
+        }
+
+        //More specific than either wildcard impl
+        impl for <[i32, i64], [i8, String]>
+        {
+            fn describe(a: &#A, b: &#B) -> String {
+                format!("{} and {}", a, b)
+            }
+        }
+
+        impl for <u8, u64>
+        {
+            fn describe(_a: &#A, _b: &#B) -> String {
+                "anything".to_string()
+            }
+        }
+    }
+
+    assert_eq!(describe(&3, &"apples".to_string()), "3 and apples");
+    assert_eq!(describe(&3i64, &2i8), "3 and 2");
+    assert_eq!(describe(&3, &7u64), "3 with u64");
+    assert_eq!(describe(&3u8, &"apples".to_string()), "u8 with apples");
+    assert_eq!(describe(&3u8, &7u64), "anything");
+}
+
+#[test]
+fn group_test() {
+
+    double_dyn!{
+        type A: MyTrait;
+        type B: MyTrait;
+
+        group Ints = [i8, i16, i32];
+        group Floats = [f32, f64];
+        group Nums = Ints + Floats + [u8, i32];
+
+        fn add(a: &dyn MyTrait, b: &dyn MyTrait) -> f64;
+        fn describe(a: &dyn MyTrait, b: &dyn MyTrait) -> String {
+            format!("{} and {}", a.double_dyn_type_name(), b.double_dyn_type_name())
+        }
+
+        impl for <Ints, Floats>
+        {
+            fn add(a: &#A, b: &#B) -> f64 {
+                *a as f64 + *b as f64
+            }
+        }
+
+        impl for <[u8, String], Nums>
+        {
+            fn add(_a: &#A, _b: &#B) -> f64 {
+                0.0
+            }
+            fn describe(_a: &#A, _b: &#B) -> String {
+                "no".to_string()
+            }
+        }
+    }
+
+    assert_eq!(add(&2i16, &0.5f32), 2.5);
+    assert_eq!(add(&2u8, &0.5f32), 0.0);
+    assert!(can_add(&"a".to_string(), &2u8));
+    assert!(!can_add(&2i32, &2i32));
+    assert_eq!(describe(&2i8, &2i32), "i8 and i32");
+    assert_eq!(describe(&2u8, &2i32), "no");
+}
+
+#[test]
+fn except_test() {
+
+    double_dyn!{
+        type A: MyTrait;
+        type B: MyTrait;
+
+        fn multiply(a: &dyn MyTrait, b: &dyn MyTrait) -> String {
+            format!("can't multiply {} by {}", a.double_dyn_type_name(), b.double_dyn_type_name())
+        }
+
+        impl for <[i8, i64, i128], [f32, f64]> except <i128, f32>, <[i8, i64], f64>
+        {
+            fn multiply(a: &#A, b: &#B) -> String {
+                format!("{}", *a as #B * *b)
+            }
+        }
+
+        impl for <i64, f64>
+        {
+            fn multiply(a: &i64, b: &f64) -> String {
+                format!("{} (i64 by f64)", *a as f64 * *b)
+            }
+        }
+
+        #[commutative]
+        impl for <[u8, u16], [u8, u16]> except <u8, u16>
+        {
+            fn multiply(a: &#A, b: &#B) -> String {
+                format!("{}", *a as u32 * *b as u32)
+            }
+        }
+    }
+
+    assert_eq!(multiply(&2i8, &1.5f32), "3");
+    assert_eq!(multiply(&2i128, &1.5f64), "3");
+    assert_eq!(multiply(&2i128, &1.5f32), "can't multiply i128 by f32");
+    assert_eq!(multiply(&2i8, &1.5f64), "can't multiply i8 by f64");
+    assert_eq!(multiply(&2i64, &1.5f64), "3 (i64 by f64)");
+
+    assert_eq!(multiply(&2u16, &3u16), "6");
+    assert_eq!(multiply(&2u8, &3u16), "can't multiply u8 by u16");
+    assert_eq!(multiply(&2u16, &3u8), "can't multiply u16 by u8");
+}
+
+#[test]
+fn zip_and_diagonal_test() {
+
+    double_dyn!{
+        type A: MyTrait;
+        type B: MyTrait;
+
+        fn widen(_a: &dyn MyTrait, _b: &dyn MyTrait) -> Option<u64> {
+            None
+        }
+        fn equals(_a: &dyn MyTrait, _b: &dyn MyTrait) -> bool {
+            false
+        }
+
+        impl for zip <[u8, u16, u32], [u16, u32, u64]>
+        {
+            fn widen(a: &#A, b: &#B) -> Option<u64> {
+                Some(#B::from(*a) as u64 + *b as u64)
+            }
+        }
+
+        impl for <T in [u8, u16, u32, u64], T>
+        {
+            fn equals(a: &#A, b: &#B) -> bool {
+                a == b
+            }
+        }
+    }
+
+    assert_eq!(widen(&1u8, &2u16), Some(3));
+    assert_eq!(widen(&1u32, &2u64), Some(3));
+    assert_eq!(widen(&1u8, &2u32), None);
+    assert!(equals(&2u16, &2u16));
+    assert!(!equals(&2u16, &3u16));
+    assert!(!equals(&2u16, &2u32));
+    assert!(can_equals(&2u64, &2u8));
+    assert_eq!(MY_TRAIT_IMPLS.len(), 7);
+}
+
+#[test]
+fn mut_args_test() {
+
+    #[derive(Debug, PartialEq)]
+    struct Ball {
+        speed: i32,
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Wall {
+        hits: u32,
+    }
+
+    double_dyn!{
+        type A: Body;
+        type B: Body;
+
+        fn collide(a: &mut dyn Body, b: &mut dyn Body, strength: i32);
+        fn describe(a: &dyn Body, b: &mut dyn Body) -> String;
+
+        impl for <Ball, Ball>
+        {
+            fn collide(a: &mut Ball, b: &mut Ball, _strength: i32) {
+                std::mem::swap(&mut a.speed, &mut b.speed);
+            }
+            fn describe(a: &Ball, b: &mut Ball) -> String {
+                b.speed += 1;
+                format!("{} and {}", a.speed, b.speed)
+            }
+        }
+
+        impl for <Ball, Wall>
+        {
+            fn collide(a: &mut Ball, b: &mut Wall, strength: i32) {
+                a.speed = -a.speed;
+                b.hits += strength as u32;
+            }
+            fn describe(_a: &Ball, _b: &mut Wall) -> String {
+                "ball and wall".to_string()
+            }
+        }
+
+        impl for <Wall, Ball>
+        {
+            fn collide(a: &mut Wall, b: &mut Ball, strength: i32) {
+                a.hits += strength as u32;
+                b.speed = -b.speed;
+            }
+            fn describe(_a: &Wall, _b: &mut Ball) -> String {
+                "wall and ball".to_string()
+            }
+        }
+    }
+
+    let mut ball_1 = Ball{speed: 1};
+    let mut ball_2 = Ball{speed: 2};
+    let mut wall = Wall{hits: 0};
+
+    collide(&mut ball_1, &mut ball_2, 1);
+    assert_eq!(ball_1, Ball{speed: 2});
+    assert_eq!(ball_2, Ball{speed: 1});
+
+    collide(&mut ball_1, &mut wall, 3);
+    collide(&mut wall, &mut ball_2, 2);
+    assert_eq!(ball_1, Ball{speed: -2});
+    assert_eq!(ball_2, Ball{speed: -1});
+    assert_eq!(wall, Wall{hits: 5});
+
+    assert_eq!(describe(&ball_1, &mut ball_2), "-2 and 0");
+    assert_eq!(describe(&wall, &mut ball_2), "wall and ball");
+    assert!(try_collide(&mut wall, &mut Wall{hits: 0}, 1).is_err());
+}
+
+#[test]
+fn boxed_args_test() {
+
+    #[derive(Debug, PartialEq)]
+    struct Words(Vec<String>);
+
+    double_dyn!{
+        type A: Mergeable;
+        type B: Mergeable;
+
+        fn merge(a: Box<dyn Mergeable>, b: Box<dyn Mergeable>) -> Box<dyn Mergeable>;
+        fn describe(a: Box<dyn Mergeable>, b: &dyn Mergeable) -> String {
+            format!("{} with {}", a.double_dyn_type_name(), b.double_dyn_type_name())
+        }
+
+        impl for <Words, Words>
+        {
+            fn merge(mut a: Words, b: Box<Words>) -> Box<dyn Mergeable> {
+                a.0.extend(b.0);
+                Box::new(a)
+            }
+        }
+
+        impl for <Words, String>
+        {
+            fn merge(mut a: Box<Words>, b: String) -> Box<dyn Mergeable> {
+                a.0.push(b);
+                a
+            }
+            fn describe(a: Words, b: &String) -> String {
+                format!("{} words and {}", a.0.len(), b)
+            }
+        }
+    }
+
+    let words = merge(Box::new(Words(vec!["a".to_string()])), Box::new("b".to_string()));
+    let words = merge(words, Box::new(Words(vec!["c".to_string()])));
+    assert_eq!(describe(words, &"d".to_string()), "3 words and d");
+    assert_eq!(describe(Box::new("a".to_string()), &"b".to_string()), "String with String");
+    assert!(try_merge(Box::new("a".to_string()), Box::new("b".to_string())).is_err());
+}
+
+#[test]
+fn shared_args_test() {
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    struct Node {
+        children: std::cell::RefCell<Vec<Rc<dyn Attach>>>,
+    }
+
+    double_dyn!{
+        type A: Attach;
+        type B: Attach;
+
+        fn attach(parent: Rc<dyn Attach>, child: Rc<dyn Attach>) -> usize;
+
+        impl for <Node, [Node, String]>
+        {
+            fn attach(parent: Rc<Node>, child: Rc<#B>) -> usize {
+                parent.children.borrow_mut().push(child);
+                parent.children.borrow().len()
+            }
+        }
+    }
+
+    double_dyn!{
+        type A: Shared: Send + Sync;
+        type B: Shared;
+
+        fn pick(a: Arc<dyn Shared>, b: Arc<dyn Shared>) -> Arc<dyn Shared>;
+
+        impl for <[i32, String], [i32, String]>
+        {
+            fn pick(a: Arc<#A>, b: Arc<#B>) -> Arc<dyn Shared> {
+                if Shared::double_dyn_type_name(&*a) == "i32" {
+                    a
+                } else {
+                    b
+                }
+            }
+        }
+    }
+
+    let root = Rc::new(Node{children: Default::default()});
+    let child: Rc<dyn Attach> = Rc::new("leaf".to_string());
+    assert_eq!(attach(root.clone(), child.clone()), 1);
+    assert_eq!(attach(root.clone(), Rc::new(Node{children: Default::default()})), 2);
+    assert_eq!(Rc::strong_count(&child), 2);
+    assert!(try_attach(child, root).is_err());
+
+    let int: Arc<dyn Shared> = Arc::new(5);
+    let string: Arc<dyn Shared> = Arc::new("b".to_string());
+    assert!(Arc::ptr_eq(&pick(int.clone(), string.clone()), &int));
+    assert!(Arc::ptr_eq(&pick(string.clone(), string.clone()), &string));
+    let handle = std::thread::spawn(move || Shared::double_dyn_type_name(&*pick(string, int)));
+    assert_eq!(handle.join().unwrap(), "i32");
+}
+
+#[test]
+fn optional_args_test() {
+
+    struct Circle(f32);
+    struct Square(f32);
+    struct Paint;
+    struct Chrome;
+
+    double_dyn!{
+        type A: Outline;
+        type B: Material;
+
+        fn render(shape: &dyn Outline, material: Option<&dyn Material>) -> String;
+
+        impl for <[Circle, Square], Paint>
+        {
+            fn render(shape: &#A, _material: &Paint) -> String {
+                format!("painted {}", shape.0)
+            }
+        }
+
+        impl for <Circle, Chrome>
+        {
+            fn render(shape: &Circle, _material: &Chrome) -> String {
+                format!("shiny {}", shape.0)
+            }
+        }
+
+        impl for <Circle, None>
+        {
+            fn render(shape: &Circle, _material: Option<&dyn Material>) -> String {
+                format!("bare {}", shape.0)
+            }
+        }
+    }
+
+    double_dyn!{
+        type A: Fill;
+        type B: Pattern;
+
+        fn fill(shape: &dyn Fill, pattern: Option<&dyn Pattern>) -> String {
+            format!("{} with {}", shape.double_dyn_type_name(), pattern.map_or("nothing", |pattern| pattern.double_dyn_type_name()))
+        }
+
+        impl for <Circle, Paint>
+        {
+            fn fill(_shape: &Circle, _pattern: &Paint) -> String {
+                "solid circle".to_string()
+            }
+        }
+
+        impl for <Circle, None>
+        {
+            fn fill(_shape: &Circle, _pattern: Option<&dyn Pattern>) -> String {
+                "solid circle".to_string()
+            }
+        }
+
+        impl for <Square, Chrome>
+        {
+            fn fill(_shape: &Square, _pattern: &Chrome) -> String {
+                "chrome square".to_string()
+            }
+        }
+    }
+
+    assert_eq!(render(&Circle(1.0), Some(&Paint)), "painted 1");
+    assert_eq!(render(&Square(2.0), Some(&Paint)), "painted 2");
+    assert_eq!(render(&Circle(3.0), Some(&Chrome)), "shiny 3");
+    assert_eq!(render(&Circle(4.0), None), "bare 4");
+    assert_eq!(try_render(&Square(5.0), None).unwrap_err().type_names, vec![std::any::type_name::<Square>(), "None"]);
+    assert!(try_render(&Square(5.0), Some(&Chrome)).is_err());
+    assert!(can_render(&Circle(0.0), None));
+    assert!(!can_render(&Square(0.0), None));
+    assert!(OUTLINE_IMPLS.contains(&double_dyn::ImplInfo{fn_name: "render", type_names: &["Circle", "None"]}));
+
+    assert_eq!(fill(&Circle(0.0), Some(&Paint)), "solid circle");
+    assert_eq!(fill(&Circle(0.0), None), "solid circle");
+    assert_eq!(fill(&Square(0.0), Some(&Chrome)), "chrome square");
+    assert_eq!(fill(&Square(0.0), None), "Square with nothing");
+    assert_eq!(fill(&Circle(0.0), Some(&Chrome)), "Circle with Chrome");
+}
+
+#[test]
+fn reduce_test() {
+
+    double_dyn!{
+        type A: Num;
+        type B: Num;
+
+        #[reduce]
+        fn add(a: &dyn Num, b: &dyn Num) -> Box<dyn Num>;
+
+        impl for <[i32, i64], [i32, i64]>
+        {
+            fn add(a: &#A, b: &#B) -> Box<dyn Num> {
+                Box::new(*a as i64 + *b as i64)
+            }
+        }
+
+        impl for <[i32, i64, f64], f64>
+        {
+            fn add(a: &#A, b: &f64) -> Box<dyn Num> {
+                Box::new(*a as f64 + b)
+            }
+        }
+    }
+
+    let sum = add_fold(&[&1i32, &2i64, &3i32]).unwrap();
+    assert_eq!(sum.double_dyn_type_name(), "i64");
+
+    let sum = add_fold(&[&1i32, &2i64, &0.5f64]).unwrap();
+    assert_eq!(sum.double_dyn_type_name(), "f64");
+
+    assert_eq!(add_fold(&[]).err(), Some(double_dyn::FoldError::EmptySlice{fn_name: "add"}));
+
+    //A single item is returned as a copy
+    let only = add_fold(&[&7i32]).unwrap();
+    assert_eq!(only.double_dyn_type_name(), "i32");
+    assert_eq!(add_fold(&[&*only, &0.5f64]).unwrap().double_dyn_type_name(), "f64");
+
+    //An f64 can't be followed by an i32
+    match add_fold(&[&1i32, &0.5f64, &3i32]) {
+        Err(double_dyn::FoldError::Dispatch(err)) => assert_eq!(err.type_names, vec!["f64", "i32"]),
+        _ => panic!("expected a dispatch error"),
+    }
+}
+
+#[test]
+fn pairs_test() {
+
+    struct Circle(f32);
+    struct Square(f32);
+    struct Ray;
+
+    double_dyn!{
+        type A: Collider;
+        type B: Collider;
+
+        #[pairs]
+        fn overlap(a: &dyn Collider, b: &dyn Collider) -> f32;
+
+        #[commutative]
+        impl for <Circle, Square>
+        {
+            fn overlap(a: &#A, b: &#B) -> f32 {
+                a.0 + b.0
+            }
+        }
+
+        impl for <Circle, Circle>
+        {
+            fn overlap(a: &Circle, b: &Circle) -> f32 {
+                a.0 * b.0
+            }
+        }
+    }
+
+    double_dyn!{
+        type A: Probe;
+        type B: Target;
+
+        #[pairs]
+        fn hit(target: &dyn Target, probe: &dyn Probe) -> String;
+
+        impl for <Ray, [Circle, Square]>
+        {
+            fn hit(target: &#B, _probe: &Ray) -> String {
+                format!("ray hit {}", target.0)
+            }
+        }
+
+        impl for <Circle, Ray>
+        {
+            fn hit(_target: &Ray, _probe: &Circle) -> String {
+                "circle hit ray".to_string()
+            }
+        }
+    }
+
+    //Square-Square pairs aren't implemented, so they are skipped
+    let items: Vec<Box<dyn Collider>> = vec![Box::new(Circle(2.0)), Box::new(Square(3.0)), Box::new(Circle(4.0)), Box::new(Square(5.0))];
+    let mut overlaps = vec![];
+    overlap_all_pairs(&items, |a_idx, b_idx, result| overlaps.push((a_idx, b_idx, result)));
+    assert_eq!(overlaps, vec![(0, 1, 5.0), (0, 2, 8.0), (0, 3, 7.0), (1, 2, 7.0), (2, 3, 9.0)]);
+
+    let probes: Vec<Box<dyn Probe>> = vec![Box::new(Ray), Box::new(Circle(0.0))];
+    let targets: Vec<Box<dyn Target>> = vec![Box::new(Square(1.0)), Box::new(Ray), Box::new(Circle(2.0))];
+    let mut hits = vec![];
+    hit_bipartite_pairs(&probes, &targets, |probe_idx, target_idx, result| hits.push((probe_idx, target_idx, result)));
+    assert_eq!(hits, vec![(0, 0, "ray hit 1".to_string()), (0, 2, "ray hit 2".to_string()), (1, 1, "circle hit ray".to_string())]);
+}
+
+#[test]
+fn batch_test() {
+
+    double_dyn!{
+        type A: Factor;
+        type B: Factor;
+
+        #[batch]
+        fn multiply(a: &dyn Factor, b: &dyn Factor) -> i64 {
+            a.double_dyn_type_name().len() as i64 + b.double_dyn_type_name().len() as i64
+        }
+
+        impl for <[i32, i64], [i32, i64]>
+        {
+            fn multiply(a: &#A, b: &#B) -> i64 {
+                *a as i64 * *b as i64
+            }
+        }
+
+        impl for <String, i32>
+        {
+            fn multiply(a: &String, b: &i32) -> i64 {
+                a.len() as i64 * *b as i64
+            }
+        }
+    }
+
+    let word = "abc".to_string();
+    let items: [(&dyn Factor, &dyn Factor); 6] = [(&2i32, &3i64), (&word, &2i32), (&4i64, &5i32), (&2i32, &word), (&6i32, &7i32), (&word, &3i32)];
+    let results = multiply_batch(&items);
+    let expected: Vec<i64> = items.iter().map(|(a, b)| multiply(*a, *b)).collect();
+    assert_eq!(results, expected);
+    assert_eq!(results, vec![6, 6, 20, 9, 42, 9]);
+    assert!(multiply_batch(&[]).is_empty());
+}
+
+#[test]
+fn table_backend_test() {
+
+    //A type with the same name as an item the table backend declares doesn't collide with it
+    struct Entry(i64);
+
+    double_dyn!{
+        #![backend(table)]
+        type A: Factor;
+        type B: Factor;
+
+        fn multiply(a: &dyn Factor, b: &dyn Factor) -> i64;
+
+        fn offset(_a: &dyn Factor, _b: &dyn Factor, entry: &Entry) -> i64 {
+            entry.0
+        }
+
+        fn concat(a: Box<dyn Factor>, _b: Option<&dyn Factor>) -> String {
+            format!("{} and something", a.double_dyn_type_name())
+        }
+
+        impl for <[i32, i64], [i32, i64]>
+        {
+            fn multiply(a: &#A, b: &#B) -> i64 {
+                *a as i64 * *b as i64
+            }
+
+            fn concat(a: #A, b: &#B) -> String {
+                format!("{}{}", a, b)
+            }
+
+            fn offset(a: &#A, b: &#B, entry: &Entry) -> i64 {
+                entry.0 + *a as i64 - *b as i64
+            }
+        }
+
+        impl for <String, i32>
+        {
+            fn multiply(a: &String, b: &i32) -> i64 {
+                a.len() as i64 * *b as i64
+            }
+        }
+
+        impl for <[i32, String], None>
+        {
+            fn concat(a: #A, _b: Option<&dyn Factor>) -> String {
+                format!("{} alone", a)
+            }
+        }
+    }
+
+    assert_eq!(multiply(&2i32, &3i64), 6);
+    assert_eq!(multiply(&4i64, &5i64), 20);
+    assert_eq!(multiply(&"abc".to_string(), &2i32), 6);
+    assert_eq!(try_multiply(&2i32, &"abc".to_string()).unwrap_err().type_names, vec!["i32", "alloc::string::String"]);
+    assert!(can_multiply(&"abc".to_string(), &2i32));
+    assert!(!can_multiply(&"abc".to_string(), &2i64));
+
+    assert_eq!(concat(Box::new(1i32), Some(&2i64)), "12");
+    assert_eq!(concat(Box::new("a".to_string()), None), "a alone");
+    assert_eq!(concat(Box::new(3i64), None), "i64 and something");
+    assert_eq!(concat(Box::new("a".to_string()), Some(&2i32)), "String and something");
+    assert_eq!(offset(&5i32, &3i64, &Entry(10)), 12);
+    assert_eq!(offset(&"a".to_string(), &3i32, &Entry(10)), 10);
+
+    //A type implemented by hand with an ordinal that is out of range has no implementations
+    struct Unknown;
+    impl Factor for Unknown {
+        fn double_dyn_type_name(&self) -> &'static str {
+            "Unknown"
+        }
+    }
+    unsafe impl FactorOrdinal for Unknown {
+        fn double_dyn_type_ordinal(&self) -> usize {
+            usize::MAX
+        }
+    }
+    assert!(!can_multiply(&2i32, &Unknown));
+    assert_eq!(try_multiply(&Unknown, &2i32).unwrap_err().type_names, vec!["{unknown}", "i32"]);
+}
+
+#[test]
+fn enum_backend_test() {
+
+    double_dyn!{
+        #![backend(enum)]
+        type A: Operand;
+        type B: Operand;
+
+        fn multiply(a: &dyn Operand, b: &dyn Operand) -> i64;
+
+        fn scale(a: &mut dyn Operand, b: &dyn Operand) {
+            let _ = (a, b);
+        }
+
+        impl for <[i32, i64], [i32, i64]>
+        {
+            fn multiply(a: &#A, b: &#B) -> i64 {
+                *a as i64 * *b as i64
+            }
+
+            fn scale(a: &mut #A, b: &#B) {
+                *a *= *b as #A;
+            }
+        }
+
+        impl for <String, i32>
+        {
+            fn multiply(a: &String, b: &i32) -> i64 {
+                a.len() as i64 * *b as i64
+            }
+        }
+    }
+
+    let mut a = Operand::from(2i32);
+    let b = Operand::I64(3);
+    assert_eq!(multiply(&a, &b), 6);
+    assert_eq!(multiply(&Operand::from("abc".to_string()), &Operand::from(2i32)), 6);
+
+    let err = try_multiply(&Operand::from(2i32), &Operand::from("abc".to_string())).unwrap_err();
+    assert_eq!(err.type_names, vec!["i32", "alloc::string::String"]);
+    assert!(can_multiply(&a, &b));
+    assert!(!can_multiply(&b, &Operand::from("abc".to_string())));
+
+    scale(&mut a, &b);
+    assert!(matches!(a, Operand::I32(6)));
+    scale(&mut a, &Operand::from("abc".to_string()));
+    assert_eq!(a.double_dyn_type_name(), "i32");
+    assert_eq!(b.double_dyn_type_ordinal(), 1);
+}
+
+#[test]
+fn registry_backend_test() {
+
+    //A type with the same name as a role doesn't collide with the type params of the registry
+    struct A(i64);
+
+    double_dyn!{
+        #![backend(registry)]
+        type A: Factor;
+        type B: Factor;
+
+        fn multiply(a: &dyn Factor, b: &dyn Factor, scale: i64) -> i64;
+
+        fn offset(_a: &dyn Factor, _b: &dyn Factor, base: A) -> i64 {
+            base.0
+        }
+
+        fn describe(a: &dyn Factor, b: &dyn Factor) -> String {
+            format!("{} and {}", a.double_dyn_type_name(), b.double_dyn_type_name())
+        }
+
+        impl for <[i32, i64], [i32, i64]>
+        {
+            fn multiply(a: &#A, b: &#B, scale: i64) -> i64 {
+                *a as i64 * *b as i64 * scale
+            }
+
+            fn offset(a: &#A, b: &#B, base: A) -> i64 {
+                base.0 + *a as i64 - *b as i64
+            }
+        }
+
+        impl for <String, i32>
+        {
+            fn multiply(a: &String, b: &i32, scale: i64) -> i64 {
+                a.len() as i64 * *b as i64 * scale
+            }
+
+            fn describe(a: &String, b: &i32) -> String {
+                format!("{} {}", a, b)
+            }
+        }
+    }
+
+    struct Meters(i64);
+    impl Factor for Meters {
+        fn double_dyn_as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+    }
+
+    assert_eq!(multiply(&2i32, &3i64, 2), 12);
+    assert_eq!(multiply(&"abc".to_string(), &2i32, 1), 6);
+    assert_eq!(describe(&"abc".to_string(), &2i32), "abc 2");
+    assert_eq!(describe(&2i32, &"abc".to_string()), "i32 and alloc::string::String");
+
+    //A pair registered at runtime may use a type that is declared outside the invocation
+    let err = try_multiply(&Meters(2), &3i32, 1).unwrap_err();
+    assert_eq!(err.fn_name, "multiply");
+    assert!(err.type_names[0].ends_with("Meters"));
+    assert!(!can_multiply(&Meters(2), &3i32));
+
+    MULTIPLY.register::<Meters, i32>(|a, b, scale| a.0 * *b as i64 * scale);
+    assert!(can_multiply(&Meters(2), &3i32));
+    assert_eq!(multiply(&Meters(2), &3i32, 10), 60);
+
+    //Registering an existing pair replaces its implementation
+    MULTIPLY.register::<i32, i32>(|a, b, _scale| (*a + *b) as i64);
+    assert_eq!(multiply(&2i32, &3i32, 10), 5);
+
+    assert_eq!(offset(&5i32, &3i64, A(10)), 12);
+    assert_eq!(offset(&"abc".to_string(), &3i32, A(10)), 10);
+    OFFSET.register::<Meters, i32>(|a, b, base| base.0 + a.0 * *b as i64);
+    assert_eq!(offset(&Meters(2), &3i32, A(10)), 16);
+}
+
+#[test]
+fn static_dispatch_test() {
+
+    //A type with the same name as a role doesn't collide with the generic params of the companions
+    struct B(i64);
+
+    double_dyn!{
+        type A: Factor;
+        type B: Factor;
+
+        #[static_dispatch]
+        fn multiply(a: &dyn Factor, b: &dyn Factor) -> i64;
+
+        #[static_dispatch]
+        fn absorb(scale: i64, a: Box<dyn Factor>, b: &mut dyn Factor);
+
+        #[static_dispatch]
+        fn offset(a: &dyn Factor, b: &dyn Factor, base: &B) -> i64;
+
+        impl for <[i32, i64], [i32, i64]>
+        {
+            fn multiply(a: &#A, b: &#B) -> i64 {
+                *a as i64 * *b as i64
+            }
+
+            fn absorb(scale: i64, a: #A, b: &mut #B) {
+                *b += (a as i64 * scale) as #B;
+            }
+
+            fn offset(a: &#A, b: &#B, base: &B) -> i64 {
+                base.0 + *a as i64 - *b as i64
+            }
+        }
+    }
+
+    fn square<T: MultiplyWith<T>>(val: &T) -> i64 {
+        multiply_static(val, val)
+    }
+
+    assert_eq!(multiply_static(&2i32, &3i64), 6);
+    assert_eq!(<i64 as MultiplyWith<i32>>::multiply_static(&4, &5), 20);
+    assert_eq!(square(&7i64), 49);
+    assert_eq!(multiply_static(&2i32, &3i64), multiply(&2i32, &3i64));
+
+    let mut b = 1i32;
+    absorb_static(10, Box::new(2i64), &mut b);
+    assert_eq!(b, 21);
+    assert_eq!(offset_static(&5i32, &3i64, &B(10)), 12);
+}
+
+#[double_dyn::methods]
+trait Shape: std::fmt::Debug {
+    fn area(&self) -> f32;
+}
+
+#[derive(Debug)]
+struct Square(f32);
+
+#[derive(Debug)]
+struct Circle(f32);
+
+#[double_dyn::methods]
+impl Shape for Square {
+    fn area(&self) -> f32 {
+        self.0 * self.0
+    }
+}
+
+#[double_dyn::methods]
+impl Shape for Circle {
+    fn area(&self) -> f32 {
+        3.0 * self.0 * self.0
+    }
+}
+
+double_dyn!{
+    #[methods]
+    type A: Shape;
+    type B: Shape;
+
+    #[batch]
+    fn larger(a: &dyn Shape, b: &dyn Shape) -> bool;
+
+    fn grow(a: &mut dyn Shape, b: &dyn Shape);
+
+    impl for <[Square, Circle], [Square, Circle]>
+    {
+        fn larger(a: &#A, b: &#B) -> bool {
+            a.area() > b.area()
+        }
+
+        fn grow(a: &mut #A, b: &#B) {
+            a.0 += b.area();
+        }
+    }
+}
+
+#[test]
+fn methods_test() {
+    let mut shapes: Vec<Box<dyn Shape>> = vec![Box::new(Square(2.0)), Box::new(Circle(1.0))];
+
+    assert!(larger(&*shapes[0], &*shapes[1]));
+    assert!(!larger(&*shapes[1], &*shapes[0]));
+    assert_eq!(larger_batch(&[(&*shapes[0], &*shapes[1]), (&*shapes[1], &*shapes[0])]), vec![true, false]);
+    assert_eq!(shapes[1].double_dyn_type_name(), "Circle");
+
+    let square = shapes.remove(0);
+    grow(&mut *shapes[0], &*square);
+    assert_eq!(shapes[0].area(), 75.0);
+    assert_eq!(format!("{:?}", shapes[0]), "Circle(5.0)");
+}
+
+#[test]
+fn upcast_test() {
+
+    double_dyn!{
+        #[upcast]
+        type A: Factor;
+        type B: Factor;
+
+        fn multiply(a: &dyn Factor, b: &dyn Factor) -> i64;
+
+        fn scale(a: &mut dyn Factor, b: &dyn Factor);
+
+        impl for <[i32, i64], [i32, i64]>
+        {
+            fn multiply(a: &#A, b: &#B) -> i64 {
+                *a as i64 * *b as i64
+            }
+
+            fn scale(a: &mut #A, b: &#B) {
+                *a *= *b as #A;
+            }
+        }
+    }
+
+    trait Labeled: AsFactor {
+        fn label(&self) -> String;
+    }
+
+    impl Labeled for i32 {
+        fn label(&self) -> String {
+            format!("{}i32", self)
+        }
+    }
+
+    fn product<T: AsFactor + ?Sized>(items: &[&T]) -> i64 {
+        items.iter().fold(1, |acc, item| acc * multiply(item.as_factor(), &1i32))
+    }
+
+    let mut labeled: Box<dyn Labeled> = Box::new(3);
+    assert_eq!(multiply(&*labeled, &2i64), 6);
+    assert_eq!(labeled.as_factor().double_dyn_type_name(), "i32");
+
+    scale(labeled.as_factor_mut(), &5i64);
+    assert_eq!(labeled.label(), "15i32");
+    assert_eq!(product::<dyn Labeled>(&[&*labeled, &*labeled]), 225);
+    assert_eq!(product(&[&2i64, &4i64]), 8);
+}
+
+mod decl {
+    double_dyn::double_dyn_decl!{
+        macro scalars;
+
+        type A: Scalar;
+        type B: Scalar;
+
+        pub fn multiply(a: &dyn Scalar, b: &dyn Scalar) -> i64;
+
+        pub fn describe(a: &dyn Scalar, b: &dyn Scalar) -> String {
+            format!("{} by {}", a.double_dyn_type_name(), b.double_dyn_type_name())
+        }
+    }
+}
+
+mod decl_ints {
+    double_dyn::double_dyn_impl!{
+        crate::decl::scalars;
+        #![collect(int_scalars)]
+
+        impl for <[i32, i64], [i32, i64]>
+        {
+            fn multiply(a: &#A, b: &#B) -> i64 {
+                *a as i64 * *b as i64
+            }
+        }
+    }
+}
+
+mod decl_impls {
+    pub struct Pair(pub i32, pub i32);
+
+    double_dyn::double_dyn_impl!{
+        crate::decl_ints::int_scalars;
+
+        impl for <Pair, [i32, i64]>
+        {
+            fn multiply(a: &Pair, b: &#B) -> i64 {
+                (a.0 + a.1) as i64 * *b as i64
+            }
+        }
+    }
+}
+
+#[test]
+fn decl_test() {
+    use decl_impls::*;
+
+    assert_eq!(multiply(&2i32, &3i64), 6);
+    assert_eq!(multiply(&Pair(1, 2), &5i32), 15);
+    assert_eq!(describe(&2i32, &Pair(1, 2)), "i32 by Pair");
+    assert!(!can_multiply(&2i64, &Pair(1, 2)));
+}
+
+mod family {
+    double_dyn::double_dyn_decl!{
+        macro lengths;
+
+        #![extensible]
+        type A: Length;
+        type B: Length;
+
+        pub fn add(a: &dyn Length, b: &dyn Length) -> i64;
+
+        pub fn describe(a: &dyn Length, b: &dyn Length) -> String {
+            format!("{} and {}", a.double_dyn_type_name(), b.double_dyn_type_name())
+        }
+    }
+
+    double_dyn::double_dyn_impl!{
+        lengths;
+
+        impl for <[i32, i64], [i32, i64]>
+        {
+            fn add(a: &#A, b: &#B) -> i64 {
+                *a as i64 + *b as i64
+            }
+        }
+    }
+}
+
+mod plugin {
+    pub struct Meters(pub i64);
+    pub struct Inches;
+
+    double_dyn::double_dyn_impl!{
+        crate::family::lengths;
+        #![extend(crate::family, [Meters, Inches])]
+
+        impl for <Meters, [i32, i64]>
+        {
+            fn add(a: &Meters, b: &#B) -> i64 {
+                a.0 + *b as i64
+            }
+        }
+
+        impl for <Meters, Meters>
+        {
+            fn add(a: &Meters, b: &Meters) -> i64 {
+                a.0 + b.0
+            }
+        }
+
+        impl for <i32, Meters>
+        {
+            fn add(a: &i32, b: &Meters) -> i64 {
+                *a as i64 + b.0
+            }
+
+            fn describe(a: &i32, b: &Meters) -> String {
+                format!("{} meters", *a as i64 + b.0)
+            }
+        }
+    }
+}
+
+#[test]
+fn extension_test() {
+    use family::*;
+    use plugin::*;
+
+    assert_eq!(add(&2i32, &3i64), 5);
+    assert_eq!(add(&Meters(2), &3i64), 5);
+    assert_eq!(add(&Meters(2), &Meters(3)), 5);
+    assert_eq!(add(&2i32, &Meters(3)), 5);
+
+    let err = try_add(&2i64, &Meters(3)).unwrap_err();
+    assert_eq!(err.type_names, vec!["i64", std::any::type_name::<Meters>()]);
+    assert!(try_add(&Inches, &Meters(3)).is_err());
+    assert!(try_add(&Meters(2), &Inches).is_err());
+
+    //The "can_" predicates answer for the combinations with the new types, like the dispatch
+    assert!(can_add(&Meters(2), &3i64));
+    assert!(can_add(&Meters(2), &Meters(3)));
+    assert!(can_add(&2i32, &Meters(3)));
+    assert!(can_add(&2i32, &3i64));
+    assert!(!can_add(&2i64, &Meters(3)));
+    assert!(!can_add(&Inches, &Meters(3)));
+
+    assert_eq!(describe(&2i32, &Meters(3)), "5 meters");
+    assert_eq!(describe(&Meters(2), &Inches), "Meters and Inches");
+    assert_eq!(describe(&2i64, &3i32), "i64 and i32");
+    assert_eq!(Inches.double_dyn_type_name(), "Inches");
+}